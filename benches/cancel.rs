@@ -0,0 +1,56 @@
+//! Cost of dropping delays before they fire, which is what most request
+//! timeouts do, with either cancellation policy.
+
+// Criterion only builds for native targets, so there's nothing to run in the
+// browser.
+#![cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), no_main)]
+#![cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+
+use std::pin::Pin;
+use std::task::Context;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures::task::noop_waker;
+use futures::Future;
+use zduny_wasm_timer::{Cancellation, Delay, Instant, Timer};
+
+const DELAYS: u64 = 1_000;
+
+fn poll_timer(timer: &mut Timer) {
+    let waker = noop_waker();
+    let _ = Pin::new(timer).poll(&mut Context::from_waker(&waker));
+}
+
+fn cancel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel");
+    for cancellation in [Cancellation::Eager, Cancellation::Lazy] {
+        let mut timer = Timer::new();
+        timer.set_cancellation(cancellation);
+        let start = Instant::now() + Duration::from_secs(3600);
+        let id = BenchmarkId::from_parameter(format!("{cancellation:?}"));
+        group.bench_function(id, |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::new(0, 0);
+                for _ in 0..iters {
+                    let delays: Vec<_> = (0..DELAYS)
+                        .map(|i| {
+                            Delay::new_handle(start + Duration::from_millis(i), timer.handle())
+                        })
+                        .collect();
+                    poll_timer(&mut timer);
+
+                    let cancelled_at = std::time::Instant::now();
+                    drop(delays);
+                    poll_timer(&mut timer);
+                    elapsed += cancelled_at.elapsed();
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cancel);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Cost of firing many delays sharing a deadline, as in retry storms, whose
+//! tasks are either all distinct or all the same.
+
+// Criterion only builds for native targets, so there's nothing to run in the
+// browser.
+#![cfg_attr(all(target_arch = "wasm32", target_os = "unknown"), no_main)]
+#![cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures::task::{self, ArcWake};
+use futures::Future;
+use zduny_wasm_timer::{Delay, Instant, Timer};
+
+const DELAYS: usize = 500;
+
+struct Task;
+
+impl ArcWake for Task {
+    fn wake_by_ref(_: &Arc<Self>) {}
+}
+
+fn fire(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fire");
+    for tasks in [DELAYS, 1] {
+        let mut timer = Timer::new();
+        let wakers: Vec<_> = (0..tasks).map(|_| task::waker(Arc::new(Task))).collect();
+        let id = BenchmarkId::new("tasks", tasks);
+        group.bench_function(id, |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::new(0, 0);
+                for _ in 0..iters {
+                    let at = Instant::now() + Duration::from_secs(3600);
+                    let mut delays: Vec<_> = (0..DELAYS)
+                        .map(|_| Delay::new_handle(at, timer.handle()))
+                        .collect();
+                    for (i, delay) in delays.iter_mut().enumerate() {
+                        let mut cx = Context::from_waker(&wakers[i % tasks]);
+                        let _ = Pin::new(delay).poll(&mut cx);
+                    }
+                    timer.fire_expired(at - Duration::from_secs(1));
+
+                    let fired_at = std::time::Instant::now();
+                    timer.fire_expired(at);
+                    elapsed += fired_at.elapsed();
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fire);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+//! Applies arbitrary sequences of operations to a `Heap`, checking its
+//! invariants after each of them.
+//!
+//! cargo +nightly fuzz run heap
+//!
+//! Inputs found to crash are to be added to the `fuzz_regressions` test of
+//! the heap.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    zduny_wasm_timer::heap::fuzz::run(data);
+});
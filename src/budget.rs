@@ -0,0 +1,128 @@
+//! Cooperative yielding for long CPU-bound loops.
+
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use futures::future::{self, FusedFuture};
+use futures::Future;
+
+use crate::{Delay, Instant};
+
+/// A time slice, after which a CPU-bound task should yield so that timers and,
+/// on WASM, the browser get a chance to run.
+///
+/// Yielding at every iteration of a loop is costly, and never yielding starves
+/// everything else: `maybe_yield` only yields once the slice is used up.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use zduny_wasm_timer::Budget;
+///
+/// # fn process(_: u32) {}
+/// # async fn example(items: Vec<u32>) {
+/// let mut budget = Budget::new(Duration::from_millis(8));
+/// for item in items {
+///     process(item);
+///     budget.maybe_yield().await;
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Budget {
+    slice: Duration,
+    /// Start of the current slice.
+    start: Instant,
+}
+
+impl Budget {
+    /// Creates a budget of `slice`, starting now.
+    pub fn new(slice: Duration) -> Budget {
+        Budget {
+            slice,
+            start: Instant::now(),
+        }
+    }
+
+    /// Completes at once while within the slice. Otherwise yields with
+    /// `yield_point`, and starts a new slice.
+    pub async fn maybe_yield(&mut self) {
+        if self.start.elapsed() < self.slice {
+            return;
+        }
+        yield_point().await;
+        self.start = Instant::now();
+    }
+}
+
+/// Yields to the timer, and lets it fire the delays which are due, by waiting
+/// for a zero delay.
+///
+/// On WASM this posts a message on a `MessageChannel` rather than going
+/// through `setTimeout`, which browsers clamp to a few milliseconds. The task
+/// yields at least once, even if the timer is gone.
+pub async fn yield_point() {
+    let mut delay = Delay::new(Duration::ZERO);
+    let mut yielded = false;
+    future::poll_fn(move |cx| {
+        let ready = delay.is_terminated() || Pin::new(&mut delay).poll(cx).is_ready();
+        if ready && yielded {
+            return Poll::Ready(());
+        }
+        // On native, the timer thread may fire the delay before it's first
+        // polled, in which case the task still has to go back to the executor.
+        if ready {
+            cx.waker().wake_by_ref();
+        }
+        yielded = true;
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use futures::future;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::Budget;
+    use crate::{sleep, Instant};
+
+    #[wasm_bindgen_test]
+    async fn yields_once_per_slice() {
+        let mut budget = Budget::new(Duration::from_millis(8));
+        let start = Instant::now();
+        let mut yields = 0;
+        while start.elapsed() < Duration::from_millis(80) {
+            let slice = budget.start;
+            budget.maybe_yield().await;
+            if budget.start != slice {
+                yields += 1;
+            }
+        }
+        assert!((5..=10).contains(&yields), "{}", yields);
+    }
+
+    #[wasm_bindgen_test]
+    async fn lets_delays_fire() {
+        let fired = Cell::new(false);
+        let start = Instant::now();
+        let delay = async {
+            sleep(Duration::from_millis(1)).await;
+            fired.set(true);
+        };
+        let busy = async {
+            let mut budget = Budget::new(Duration::from_millis(4));
+            while !fired.get() && start.elapsed() < Duration::from_secs(1) {
+                budget.maybe_yield().await;
+            }
+        };
+        future::join(delay, busy).await;
+        // The delay fires within a few slices, not once the loop gives up.
+        assert!(fired.get());
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,155 @@
+//! Cancelling delays from anywhere in a call tree.
+//!
+//! A `CancellationToken` is handed down to the code which creates delays,
+//! while the `CancelHandle` stays with the code which decides to cancel them.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use zduny_wasm_timer::cancel::CancellationToken;
+//! use zduny_wasm_timer::Delay;
+//!
+//! # async fn example() {
+//! let (token, handle) = CancellationToken::new();
+//! let delay = Delay::new(Duration::from_secs(60)).with_cancellation(token);
+//! handle.cancel();
+//! assert!(delay.await.is_err());
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Mutex};
+
+use crate::timer;
+use crate::DelayHandle;
+
+/// Token which cancels the delays it's attached to, with
+/// `Delay::with_cancellation`, once its `CancelHandle` is used.
+///
+/// The token can be cloned, and the clones cancel their delays together.
+#[derive(Clone)]
+pub struct CancellationToken {
+    shared: Arc<Shared>,
+}
+
+/// Handle cancelling the delays attached to a `CancellationToken`.
+#[derive(Clone)]
+pub struct CancelHandle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    cancelled: AtomicBool,
+    /// Delays attached to the token.
+    delays: Mutex<Vec<DelayHandle>>,
+}
+
+impl CancellationToken {
+    /// Creates a token, and the handle which cancels it.
+    pub fn new() -> (CancellationToken, CancelHandle) {
+        let shared = Arc::new(Shared {
+            cancelled: AtomicBool::new(false),
+            delays: Mutex::new(Vec::new()),
+        });
+        let handle = CancelHandle {
+            shared: shared.clone(),
+        };
+        (CancellationToken { shared }, handle)
+    }
+
+    /// Returns true if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(SeqCst)
+    }
+
+    /// Cancels the delay of `handle` when the token is cancelled, or right
+    /// away if it already is.
+    pub(crate) fn attach(&self, handle: DelayHandle) {
+        let mut delays = timer::lock(&self.shared.delays);
+        if self.is_cancelled() {
+            drop(delays);
+            handle.cancel();
+            return;
+        }
+        // Forgets the delays which are gone, so that a long-lived token
+        // doesn't accumulate them.
+        delays.retain(|delay| !delay.is_done());
+        delays.push(handle);
+    }
+}
+
+impl CancelHandle {
+    /// Cancels the token, which makes all the delays attached to it, now or
+    /// later, resolve with a `DelayCancelled` error.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, SeqCst);
+        let delays = std::mem::take(&mut *timer::lock(&self.shared.delays));
+        for delay in delays {
+            delay.cancel();
+        }
+    }
+
+    /// Returns true if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(SeqCst)
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelHandle")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::CancellationToken;
+    use crate::{Delay, DelayCancelled, Instant, Timer};
+
+    fn is_cancelled(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|err| err.is::<DelayCancelled>())
+    }
+
+    #[wasm_bindgen_test]
+    async fn cancel_resolves_delays() {
+        let (token, handle) = CancellationToken::new();
+        let start = Instant::now();
+        let first = Delay::new(Duration::from_secs(10)).with_cancellation(token.clone());
+        let second = Delay::new(Duration::from_secs(10)).with_cancellation(token.clone());
+        handle.cancel();
+        assert!(is_cancelled(&first.await.unwrap_err()));
+        assert!(is_cancelled(&second.await.unwrap_err()));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Delays attached after the cancellation resolve right away.
+        let late = Delay::new(Duration::from_secs(10)).with_cancellation(token);
+        assert!(is_cancelled(&late.await.unwrap_err()));
+    }
+
+    #[wasm_bindgen_test]
+    fn forgets_dropped_delays() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let (token, _handle) = CancellationToken::new();
+        for _ in 0..10 {
+            drop(Delay::new(Duration::from_secs(10)).with_cancellation(token.clone()));
+        }
+        assert_eq!(super::timer::lock(&token.shared.delays).len(), 1);
+    }
+}
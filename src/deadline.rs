@@ -0,0 +1,124 @@
+//! A delay reporting when it actually fired.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::FusedFuture;
+
+use crate::timer;
+use crate::{Delay, Instant};
+
+/// A `Delay` which resolves to the instant it actually fired at, e.g. to
+/// compute how late a frame was.
+///
+/// This is the instant the timer woke up and fired the delay, which may be
+/// later than the scheduled one, returned by `scheduled`: their difference is
+/// the jitter of the delay. If the delay isn't driven by a timer of this
+/// crate, e.g. with the `tokio` feature, it's the instant the future was
+/// polled after firing instead.
+///
+/// Like `sleep`, the future panics if the timer is gone.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use zduny_wasm_timer::deadline::Deadline;
+///
+/// # async fn example() {
+/// let deadline = Deadline::new(Duration::from_millis(16));
+/// let scheduled = deadline.scheduled();
+/// let fired = deadline.await;
+/// println!("{:?} late", fired - scheduled);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Deadline(Delay);
+
+impl Deadline {
+    /// Creates a deadline firing once `dur` has elapsed.
+    ///
+    /// The returned object will be bound to the default timer for this thread.
+    pub fn new(dur: Duration) -> Deadline {
+        Deadline(Delay::new(dur))
+    }
+
+    /// Creates a deadline firing at `at`.
+    ///
+    /// The returned object will be bound to the default timer for this thread.
+    pub fn new_at(at: Instant) -> Deadline {
+        Deadline(Delay::new_at(at))
+    }
+
+    /// Returns the instant this deadline is scheduled to fire at.
+    pub fn scheduled(&self) -> Instant {
+        self.0.deadline()
+    }
+
+    /// Returns the underlying delay.
+    pub fn into_inner(self) -> Delay {
+        self.0
+    }
+}
+
+impl From<Delay> for Deadline {
+    fn from(delay: Delay) -> Deadline {
+        Deadline(delay)
+    }
+}
+
+impl Future for Deadline {
+    type Output = Instant;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Instant> {
+        let delay = &mut self.get_mut().0;
+        match Pin::new(&mut *delay).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(res) => {
+                res.expect("timer has gone away");
+                Poll::Ready(delay.fired_at().unwrap_or_else(timer::now))
+            }
+        }
+    }
+}
+
+impl FusedFuture for Deadline {
+    fn is_terminated(&self) -> bool {
+        self.0.is_terminated()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::Deadline;
+    use crate::{Delay, Instant, Timer};
+
+    #[wasm_bindgen_test]
+    fn resolves_to_firing_instant() {
+        let mut timer = Timer::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let at = Instant::now() + Duration::from_secs(3600);
+
+        let mut deadline = Deadline::from(Delay::new_handle(at, timer.handle()));
+        assert_eq!(deadline.scheduled(), at);
+        assert_eq!(deadline.poll_unpin(&mut cx), Poll::Pending);
+        let fired = at + Duration::from_millis(7);
+        timer.fire_expired(fired);
+        assert_eq!(deadline.poll_unpin(&mut cx), Poll::Ready(fired));
+    }
+
+    #[wasm_bindgen_test]
+    async fn awaits() {
+        let deadline = Deadline::new(Duration::from_millis(10));
+        let scheduled = deadline.scheduled();
+        assert!(deadline.await >= scheduled);
+    }
+}
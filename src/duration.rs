@@ -0,0 +1,76 @@
+//! Conversions between `Duration` and the floating-point values used by
+//! JavaScript timing APIs.
+
+use std::time::Duration;
+
+/// Extension trait for `Duration`, to convert from and to floating-point
+/// values without panicking.
+///
+/// JavaScript timing APIs such as `performance.now()` and
+/// `requestAnimationFrame` work with milliseconds as `f64`.
+pub trait DurationExt {
+    /// Creates a `Duration` from a number of seconds.
+    ///
+    /// Contrary to `Duration::from_secs_f64`, this never panics: negative and
+    /// NaN values are clamped to zero, and values that are too large to be
+    /// represented are clamped to `Duration::MAX`.
+    fn from_secs_f64_clamped(secs: f64) -> Self;
+
+    /// Creates a `Duration` from a number of milliseconds.
+    ///
+    /// Out of range values are clamped like in `from_secs_f64_clamped`.
+    fn from_millis_f64(ms: f64) -> Self;
+
+    /// Returns the number of milliseconds contained by this `Duration`.
+    ///
+    /// The standard library has an unstable method with the same name, so
+    /// calling this as `DurationExt::as_millis_f64(&dur)` avoids ambiguity.
+    fn as_millis_f64(&self) -> f64;
+}
+
+impl DurationExt for Duration {
+    fn from_secs_f64_clamped(secs: f64) -> Duration {
+        if secs.is_nan() || secs <= 0.0 {
+            Duration::new(0, 0)
+        } else {
+            Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+        }
+    }
+
+    fn from_millis_f64(ms: f64) -> Duration {
+        Duration::from_secs_f64_clamped(ms / 1000.0)
+    }
+
+    fn as_millis_f64(&self) -> f64 {
+        self.as_secs_f64() * 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::DurationExt;
+
+    #[wasm_bindgen_test]
+    fn clamps_out_of_range_values() {
+        assert_eq!(Duration::from_secs_f64_clamped(-1.0), Duration::new(0, 0));
+        assert_eq!(
+            Duration::from_secs_f64_clamped(f64::NAN),
+            Duration::new(0, 0)
+        );
+        assert_eq!(
+            Duration::from_secs_f64_clamped(f64::INFINITY),
+            Duration::MAX
+        );
+        assert_eq!(
+            Duration::from_secs_f64_clamped(1.5),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(Duration::from_millis_f64(-3.0), Duration::new(0, 0));
+        assert_eq!(Duration::from_millis_f64(2.5), Duration::from_micros(2500));
+        assert_eq!(DurationExt::as_millis_f64(&Duration::from_millis(16)), 16.0);
+    }
+}
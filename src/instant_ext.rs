@@ -0,0 +1,94 @@
+//! Human-readable instants, relative to the start of the process.
+
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::Instant;
+
+/// Instant the process is considered to have started at, i.e. the first time
+/// it's needed.
+static START: OnceLock<Instant> = OnceLock::new();
+
+fn start() -> Instant {
+    *START.get_or_init(Instant::now)
+}
+
+/// Extension trait for `Instant`, to display it in logs.
+pub trait InstantExt {
+    /// Returns a value displaying the time elapsed between the start of the
+    /// process and this instant, e.g. `+1.234s`.
+    ///
+    /// The start of the process is approximated by the first call to this
+    /// method, so earlier instants are displayed with a `-` sign.
+    fn format_since_start(&self) -> SinceStart;
+}
+
+impl InstantExt for Instant {
+    fn format_since_start(&self) -> SinceStart {
+        let start = start();
+        match self.checked_duration_since(start) {
+            Some(elapsed) => SinceStart {
+                elapsed,
+                before: false,
+            },
+            None => SinceStart {
+                elapsed: start.saturating_duration_since(*self),
+                before: true,
+            },
+        }
+    }
+}
+
+/// An instant displayed relative to the start of the process, returned by
+/// `InstantExt::format_since_start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinceStart {
+    elapsed: Duration,
+    /// Whether the instant is before the start rather than after it.
+    before: bool,
+}
+
+impl fmt::Display for SinceStart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.before { '-' } else { '+' };
+        write!(
+            f,
+            "{}{}.{:03}s",
+            sign,
+            self.elapsed.as_secs(),
+            self.elapsed.subsec_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{start, InstantExt};
+    use crate::Instant;
+
+    fn secs(formatted: &str) -> f64 {
+        formatted.trim_end_matches('s').parse().unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn formats_since_start() {
+        let start = start();
+        let at = start + Duration::from_millis(1234);
+        assert_eq!(at.format_since_start().to_string(), "+1.234s");
+        if let Some(before) = start.checked_sub(Duration::from_millis(500)) {
+            assert_eq!(before.format_since_start().to_string(), "-0.500s");
+        }
+
+        let first = Instant::now();
+        while Instant::now() - first < Duration::from_millis(10) {}
+        let second = Instant::now();
+        let first = secs(&first.format_since_start().to_string());
+        let second = secs(&second.format_since_start().to_string());
+        assert!(second >= first + 0.010, "{} then {}", first, second);
+    }
+}
@@ -0,0 +1,102 @@
+//! Smoothing the rate of events, e.g. of log lines or UI updates.
+
+use std::time::Duration;
+
+use crate::timer;
+use crate::{sleep_until, DurationExt, Instant};
+
+/// A leaky bucket: events are let through one at a time, evenly spaced at a
+/// constant rate, whatever the rate they come in at.
+///
+/// Contrary to `RateLimiter`, no burst is allowed, which suits throttled
+/// logging, UI updates or polling.
+///
+/// ```no_run
+/// use zduny_wasm_timer::leaky_bucket::LeakyBucket;
+///
+/// # fn render() {}
+/// # async fn example() {
+/// // At most 30 renders per second.
+/// let mut bucket = LeakyBucket::new(30.0);
+/// loop {
+///     bucket.wait().await;
+///     render();
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LeakyBucket {
+    /// Time between two events.
+    interval: Duration,
+    /// Slot of the last event let through, if any.
+    last: Option<Instant>,
+}
+
+impl LeakyBucket {
+    /// Creates a bucket letting `rate` events per second through.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` isn't a positive finite number.
+    pub fn new(rate: f64) -> LeakyBucket {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "rate must be positive and finite"
+        );
+        LeakyBucket {
+            interval: Duration::from_secs_f64_clamped(1.0 / rate),
+            last: None,
+        }
+    }
+
+    /// Waits until the next event can be let through.
+    ///
+    /// The first event goes through at once, and the next ones `1 / rate`
+    /// seconds after the previous one. An event coming in later than that goes
+    /// through at once as well, and the next ones are spaced from it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timer is gone, like `sleep`.
+    pub async fn wait(&mut self) {
+        let now = timer::now();
+        let slot = match self.last {
+            Some(last) => timer::saturating_add(last, self.interval).max(now),
+            None => now,
+        };
+        self.last = Some(slot);
+        if slot > now {
+            sleep_until(slot).await.expect("timer has gone away");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::LeakyBucket;
+    use crate::Instant;
+
+    #[wasm_bindgen_test]
+    async fn spaces_events() {
+        let mut bucket = LeakyBucket::new(1000.0);
+        let start = Instant::now();
+        for _ in 0..100 {
+            bucket.wait().await;
+        }
+        // The first event goes through at once.
+        let elapsed = Instant::now() - start;
+        assert!(elapsed >= Duration::from_millis(98), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn tiny_rate() {
+        // The interval doesn't fit in a `Duration`.
+        let bucket = LeakyBucket::new(1e-300);
+        assert_eq!(bucket.interval, Duration::MAX);
+    }
+}
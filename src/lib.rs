@@ -18,13 +18,92 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+//! Without the default `std` feature, only the `heap` module is available,
+//! and the crate is `no_std` (it still requires `alloc`).
+//!
+//! The `prelude` module re-exports the most commonly used items:
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # async fn example() {
+//! use zduny_wasm_timer::prelude::*;
+//!
+//! let deadline = Instant::now() + Duration::from_secs(1);
+//! sleep_until(deadline).await.unwrap();
+//! # }
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub use budget::{yield_point, Budget};
+#[cfg(feature = "std")]
+pub use duration::DurationExt;
+#[cfg(feature = "std")]
+pub use instant_ext::{InstantExt, SinceStart};
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub use notify::Notify;
+#[cfg(feature = "std")]
+pub use system_time_ext::SystemTimeExt;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub use time_it::{time_it, time_it_with_label};
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
 pub use timer::*;
+#[cfg(feature = "std")]
+pub use util::{instant_from_system_time, next_aligned};
 
-#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+#[cfg(all(feature = "std", target_arch = "wasm32", target_os = "unknown"))]
+pub use spawn::*;
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
 pub use std::time::{Instant, SystemTime, UNIX_EPOCH};
-#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+#[cfg(all(feature = "std", target_arch = "wasm32", target_os = "unknown"))]
 pub use wasm::*;
 
+// With `--cfg wasm_timer_loom`, only the lock-free list of the timer is built,
+// for its loom tests.
+#[cfg(wasm_timer_loom)]
+#[allow(dead_code)]
+#[path = "timer/arc_list.rs"]
+mod arc_list;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+mod budget;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod cancel;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod deadline;
+#[cfg(feature = "std")]
+mod duration;
+#[cfg(not(feature = "std"))]
+#[path = "timer/heap.rs"]
+pub mod heap;
+#[cfg(feature = "std")]
+mod instant_ext;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod leaky_bucket;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+mod notify;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod oneshot;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod prelude;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod rate_limiter;
+#[cfg(all(feature = "std", target_arch = "wasm32", target_os = "unknown"))]
+mod spawn;
+#[cfg(feature = "std")]
+mod system_time_ext;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+mod time_it;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
 mod timer;
-#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+#[cfg(feature = "std")]
+mod util;
+#[cfg(all(feature = "std", target_arch = "wasm32", target_os = "unknown"))]
 mod wasm;
+#[cfg(all(feature = "std", not(wasm_timer_loom)))]
+pub mod watch;
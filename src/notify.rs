@@ -0,0 +1,120 @@
+//! Waking a task from another one, e.g. from a timer-driven task.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::SeqCst;
+use std::task::Poll;
+
+use futures::future;
+use futures::task::AtomicWaker;
+
+/// Notifies a single task waiting on `notified`.
+///
+/// A notification sent while no task waits isn't lost: it's stored, and
+/// consumed by the next call to `notified`. Notifications don't accumulate,
+/// though, and only one waiting task is kept track of: if several tasks wait
+/// at once, only the one which polled last is woken.
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use zduny_wasm_timer::{Delay, Notify};
+///
+/// # fn spawn(_: impl std::future::Future<Output = ()> + 'static) {}
+/// # async fn example() {
+/// let notify = Arc::new(Notify::new());
+/// let notifier = notify.clone();
+/// spawn(async move {
+///     Delay::new(Duration::from_secs(1)).await.unwrap();
+///     notifier.notify_one();
+/// });
+/// notify.notified().await;
+/// # }
+/// ```
+pub struct Notify {
+    /// Whether a notification was sent and not consumed yet.
+    notified: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Notify {
+    /// Creates a `Notify` without any stored notification.
+    pub fn new() -> Notify {
+        Notify {
+            notified: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Wakes the task waiting on `notified`, or stores the notification for
+    /// the next call to `notified` if no task is waiting.
+    pub fn notify_one(&self) {
+        self.notified.store(true, SeqCst);
+        self.waker.wake();
+    }
+
+    /// Waits for a notification, and consumes it.
+    pub fn notified(&self) -> impl Future<Output = ()> + '_ {
+        future::poll_fn(move |cx| {
+            if self.notified.swap(false, SeqCst) {
+                return Poll::Ready(());
+            }
+            self.waker.register(cx.waker());
+            // A notification sent before the registration didn't wake us.
+            if self.notified.swap(false, SeqCst) {
+                return Poll::Ready(());
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notify")
+            .field("notified", &self.notified.load(SeqCst))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::Notify;
+    use crate::Delay;
+
+    #[wasm_bindgen_test]
+    async fn timer_task_notifies() {
+        let notify = Notify::new();
+        let notifier = async {
+            Delay::new(Duration::from_millis(10)).await.unwrap();
+            notify.notify_one();
+        };
+        future::join(notify.notified(), notifier).await;
+    }
+
+    #[wasm_bindgen_test]
+    fn stores_one_notification() {
+        let notify = Notify::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        notify.notify_one();
+        notify.notify_one();
+        assert_eq!(notify.notified().poll_unpin(&mut cx), Poll::Ready(()));
+        assert_eq!(notify.notified().poll_unpin(&mut cx), Poll::Pending);
+    }
+}
@@ -0,0 +1,236 @@
+//! A timer whose deadline is set by one part of the code, while another one
+//! waits for it.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use zduny_wasm_timer::oneshot;
+//!
+//! # async fn example() {
+//! let (sender, receiver) = oneshot::channel();
+//! sender.fire_after(Duration::from_secs(1));
+//! assert!(receiver.await.is_some());
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::future::FusedFuture;
+use futures::prelude::*;
+
+use crate::timer;
+use crate::{Delay, Instant};
+
+/// Creates a timer channel, without any deadline set.
+pub fn channel() -> (Sender, Receiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        at: None,
+        closed: false,
+        waker: None,
+    }));
+    let sender = Sender {
+        shared: shared.clone(),
+    };
+    let receiver = Receiver {
+        shared,
+        delay: None,
+        done: false,
+    };
+    (sender, receiver)
+}
+
+struct Shared {
+    /// Deadline set by the sender, if any.
+    at: Option<Instant>,
+    /// Whether the sender cancelled the timer, or was dropped without setting
+    /// a deadline.
+    closed: bool,
+    /// Task waiting on the receiver.
+    waker: Option<Waker>,
+}
+
+/// Side of a timer channel deciding when it fires, created by `channel`.
+pub struct Sender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Sender {
+    /// Makes the receiver resolve once `dur` has elapsed, replacing any
+    /// deadline set before.
+    pub fn fire_after(&self, dur: Duration) {
+        self.fire_at(timer::saturating_add(timer::now(), dur))
+    }
+
+    /// Makes the receiver resolve at `at`, replacing any deadline set before.
+    ///
+    /// This has no effect once the timer was cancelled.
+    pub fn fire_at(&self, at: Instant) {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.closed {
+            return;
+        }
+        shared.at = Some(at);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Makes the receiver resolve to `None`, unless it fired already.
+    pub fn cancel(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.closed = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        // A deadline which is set still fires, but otherwise the receiver
+        // would never resolve.
+        let at = self.shared.lock().unwrap().at;
+        if at.is_none() {
+            self.cancel();
+        }
+    }
+}
+
+/// Side of a timer channel waiting for it to fire, created by `channel`.
+///
+/// Resolves to the instant the timer was set to fire at, or to `None` if it
+/// was cancelled, if the sender was dropped without setting a deadline, or if
+/// the timer the delay is bound to is gone. The delay is bound to the default
+/// timer of the thread polling the receiver.
+pub struct Receiver {
+    shared: Arc<Mutex<Shared>>,
+    /// Delay armed for the deadline set by the sender, along with that
+    /// deadline, if any.
+    delay: Option<(Instant, Delay)>,
+    done: bool,
+}
+
+impl Future for Receiver {
+    type Output = Option<Instant>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        let this = self.get_mut();
+        assert!(!this.done, "Receiver polled after completion");
+        let at = {
+            let mut shared = this.shared.lock().unwrap();
+            if shared.closed {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+            shared.waker = Some(cx.waker().clone());
+            match shared.at {
+                Some(at) => at,
+                None => return Poll::Pending,
+            }
+        };
+        match this.delay {
+            Some((ref mut armed, ref mut delay)) if *armed != at => {
+                delay.reset_at(at);
+                *armed = at;
+            }
+            Some(_) => {}
+            None => this.delay = Some((at, Delay::new_at(at))),
+        }
+        let delay = &mut this.delay.as_mut().unwrap().1;
+        match delay.poll_unpin(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(())) => {
+                this.done = true;
+                Poll::Ready(Some(at))
+            }
+            Poll::Ready(Err(_)) => {
+                this.done = true;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl FusedFuture for Receiver {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+impl fmt::Debug for Sender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shared = self.shared.lock().unwrap();
+        f.debug_struct("Sender")
+            .field("at", &shared.at)
+            .field("closed", &shared.closed)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Receiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("delay", &self.delay)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::channel;
+    use crate::{Instant, Timer};
+
+    #[wasm_bindgen_test]
+    fn fires_at_latest_deadline() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let at = Instant::now() + Duration::from_secs(3600);
+
+        let (sender, mut receiver) = channel();
+        assert_eq!(receiver.poll_unpin(&mut cx), Poll::Pending);
+        sender.fire_at(at);
+        assert_eq!(receiver.poll_unpin(&mut cx), Poll::Pending);
+        sender.fire_at(at + Duration::from_secs(10));
+        assert_eq!(receiver.poll_unpin(&mut cx), Poll::Pending);
+        assert_eq!(timer.fire_expired(at), 0);
+        // The deadline still fires once the sender is gone.
+        drop(sender);
+        assert_eq!(timer.fire_expired(at + Duration::from_secs(10)), 1);
+        assert_eq!(
+            receiver.poll_unpin(&mut cx),
+            Poll::Ready(Some(at + Duration::from_secs(10)))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn cancelled() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (sender, mut receiver) = channel();
+        sender.fire_after(Duration::from_secs(3600));
+        assert_eq!(receiver.poll_unpin(&mut cx), Poll::Pending);
+        sender.cancel();
+        assert_eq!(receiver.poll_unpin(&mut cx), Poll::Ready(None));
+
+        let (sender, mut receiver) = channel();
+        drop(sender);
+        assert_eq!(receiver.poll_unpin(&mut cx), Poll::Ready(None));
+    }
+}
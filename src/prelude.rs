@@ -0,0 +1,15 @@
+//! The most commonly used items of the crate, for glob imports.
+//!
+//! ```no_run
+//! use zduny_wasm_timer::prelude::*;
+//!
+//! # async fn example() {
+//! let start = Instant::now();
+//! sleep(Duration::from_millis(10)).await;
+//! Delay::new(Duration::from_millis(10)).await.unwrap();
+//! assert!(start.elapsed() >= Duration::from_millis(20));
+//! # }
+//! ```
+
+pub use crate::{sleep, sleep_until, Delay, DurationExt, Instant, Interval, SystemTime};
+pub use std::time::Duration;
@@ -0,0 +1,140 @@
+//! Limiting the rate of operations, e.g. of requests to a server.
+
+use std::time::Duration;
+
+use crate::timer;
+use crate::{sleep, DurationExt, Instant};
+
+/// A token bucket: operations take a token each, and tokens are refilled at a
+/// constant rate, up to the capacity of the bucket.
+///
+/// The bucket starts full, so that up to `capacity` operations can happen at
+/// once. Tokens are computed lazily from the time elapsed since the last
+/// operation, without any background task.
+///
+/// ```no_run
+/// use zduny_wasm_timer::rate_limiter::RateLimiter;
+///
+/// # async fn fetch(_: &str) {}
+/// # async fn example(urls: Vec<String>) {
+/// // Bursts of up to 5 requests, and 2 per second on average.
+/// let mut limiter = RateLimiter::new(5, 2.0);
+/// for url in urls {
+///     limiter.acquire().await;
+///     fetch(&url).await;
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: u32,
+    /// Tokens refilled per second.
+    refill_rate: f64,
+    tokens: f64,
+    /// When `tokens` was last brought up to date.
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a full bucket of `capacity` tokens, refilled with
+    /// `refill_rate` tokens per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, or if `refill_rate` isn't a positive
+    /// finite number.
+    pub fn new(capacity: u32, refill_rate: f64) -> RateLimiter {
+        assert!(capacity > 0, "capacity must be non-zero");
+        assert!(
+            refill_rate > 0.0 && refill_rate.is_finite(),
+            "refill rate must be positive and finite"
+        );
+        RateLimiter {
+            capacity,
+            refill_rate,
+            tokens: f64::from(capacity),
+            last_refill: timer::now(),
+        }
+    }
+
+    /// Waits until a token is available, and takes it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timer is gone, like `sleep`.
+    pub async fn acquire(&mut self) {
+        while !self.try_acquire() {
+            let missing = 1.0 - self.tokens;
+            sleep(Duration::from_secs_f64_clamped(missing / self.refill_rate)).await;
+        }
+    }
+
+    /// Takes a token if one is available, and returns whether it did.
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    /// Adds the tokens refilled since the last time, up to the capacity.
+    fn refill(&mut self) {
+        let now = timer::now();
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.refill_rate).min(f64::from(self.capacity));
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::Context;
+    use std::time::Duration;
+
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::RateLimiter;
+    use crate::{Instant, Timer};
+
+    #[wasm_bindgen_test]
+    fn try_acquire_empties_bucket() {
+        let mut limiter = RateLimiter::new(3, 0.001);
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+    }
+
+    #[wasm_bindgen_test]
+    async fn acquire_follows_rate() {
+        let mut limiter = RateLimiter::new(1, 200.0);
+        let start = Instant::now();
+        for _ in 0..21 {
+            limiter.acquire().await;
+        }
+        // The first token is available at once, and the others are refilled
+        // every 5 ms.
+        let elapsed = Instant::now() - start;
+        assert!(elapsed >= Duration::from_millis(95), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn tiny_refill_rate() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let mut limiter = RateLimiter::new(1, 1e-300);
+        assert!(limiter.try_acquire());
+
+        // The wait for the next token doesn't fit in a `Duration`.
+        let waker = noop_waker();
+        let acquire = pin!(limiter.acquire());
+        assert!(acquire.poll(&mut Context::from_waker(&waker)).is_pending());
+    }
+}
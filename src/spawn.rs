@@ -0,0 +1,217 @@
+//! Callback-based timers, for code that doesn't run inside of an executor.
+
+#![cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::time::Duration;
+
+use js_sys::Function;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::WorkerGlobalScope;
+
+/// Calls `f` once `duration` has elapsed, like JavaScript's `setTimeout`.
+///
+/// The callback is cancelled if the returned `SpawnHandle` is dropped before
+/// it fires. Use `SpawnHandle::forget` to let it run regardless.
+pub fn spawn_after(duration: Duration, f: impl FnOnce() + 'static) -> SpawnHandle {
+    let callback = Closure::once(f);
+    let handle = set_timeout(callback.as_ref().unchecked_ref(), duration);
+    SpawnHandle {
+        handle,
+        callback: ManuallyDrop::new(callback),
+    }
+}
+
+/// Handle to a callback scheduled with `spawn_after`.
+///
+/// Dropping the handle cancels the callback if it hasn't fired yet.
+pub struct SpawnHandle {
+    handle: i32,
+    callback: ManuallyDrop<Closure<dyn FnMut()>>,
+}
+
+impl SpawnHandle {
+    /// Lets the callback fire even though this handle is dropped.
+    ///
+    /// The callback is freed once it has been called.
+    pub fn forget(self) {
+        let mut this = ManuallyDrop::new(self);
+        let callback = unsafe { ManuallyDrop::take(&mut this.callback) };
+        let _ = callback.into_js_value();
+    }
+}
+
+impl Drop for SpawnHandle {
+    fn drop(&mut self) {
+        clear_timeout(self.handle);
+        unsafe { ManuallyDrop::drop(&mut self.callback) };
+    }
+}
+
+impl fmt::Debug for SpawnHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("SpawnHandle")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+/// Calls `f` every `period`, like JavaScript's `setInterval`.
+///
+/// The callback keeps being called until the returned `IntervalHandle` is
+/// dropped.
+pub fn spawn_every(period: Duration, f: impl FnMut() + 'static) -> IntervalHandle {
+    let callback = Closure::wrap(Box::new(f) as Box<dyn FnMut()>);
+    let handle = set_interval(callback.as_ref().unchecked_ref(), period);
+    IntervalHandle { handle, callback }
+}
+
+/// Handle to a callback scheduled with `spawn_every`.
+///
+/// Dropping the handle stops the interval and frees the callback.
+pub struct IntervalHandle {
+    handle: i32,
+    // Only kept alive for as long as the interval is running.
+    #[allow(dead_code)]
+    callback: Closure<dyn FnMut()>,
+}
+
+impl Drop for IntervalHandle {
+    fn drop(&mut self) {
+        clear_interval(self.handle);
+    }
+}
+
+impl fmt::Debug for IntervalHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("IntervalHandle")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+/// Calls `setTimeout` on the global object, and returns the handle of the
+/// timeout.
+fn set_timeout(callback: &Function, duration: Duration) -> i32 {
+    let millis = i32::try_from(duration.as_millis()).unwrap_or(i32::MAX);
+    if let Some(window) = web_sys::window() {
+        window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(callback, millis)
+            .unwrap()
+    } else {
+        js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .expect("no supported global object available")
+            .set_timeout_with_callback_and_timeout_and_arguments_0(callback, millis)
+            .unwrap()
+    }
+}
+
+/// Calls `clearTimeout` on the global object.
+fn clear_timeout(handle: i32) {
+    if let Some(window) = web_sys::window() {
+        window.clear_timeout_with_handle(handle);
+    } else {
+        js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .expect("no supported global object available")
+            .clear_timeout_with_handle(handle);
+    }
+}
+
+/// Calls `setInterval` on the global object, and returns the handle of the
+/// interval.
+fn set_interval(callback: &Function, period: Duration) -> i32 {
+    let millis = i32::try_from(period.as_millis()).unwrap_or(i32::MAX);
+    if let Some(window) = web_sys::window() {
+        window
+            .set_interval_with_callback_and_timeout_and_arguments_0(callback, millis)
+            .unwrap()
+    } else {
+        js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .expect("no supported global object available")
+            .set_interval_with_callback_and_timeout_and_arguments_0(callback, millis)
+            .unwrap()
+    }
+}
+
+/// Calls `clearInterval` on the global object.
+fn clear_interval(handle: i32) {
+    if let Some(window) = web_sys::window() {
+        window.clear_interval_with_handle(handle);
+    } else {
+        js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .expect("no supported global object available")
+            .clear_interval_with_handle(handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{spawn_after, spawn_every};
+    use crate::Delay;
+
+    #[wasm_bindgen_test]
+    async fn spawn_after_calls_back() {
+        let called = Rc::new(Cell::new(false));
+        let handle = {
+            let called = called.clone();
+            spawn_after(Duration::from_millis(10), move || called.set(true))
+        };
+        assert!(!called.get());
+        Delay::new(Duration::from_millis(50)).await.unwrap();
+        assert!(called.get());
+        drop(handle);
+    }
+
+    #[wasm_bindgen_test]
+    async fn forgotten_handle_still_calls_back() {
+        let called = Rc::new(Cell::new(false));
+        {
+            let called = called.clone();
+            spawn_after(Duration::from_millis(10), move || called.set(true)).forget();
+        }
+        Delay::new(Duration::from_millis(50)).await.unwrap();
+        assert!(called.get());
+    }
+
+    #[wasm_bindgen_test]
+    async fn dropping_handle_cancels() {
+        let called = Rc::new(Cell::new(false));
+        {
+            let called = called.clone();
+            drop(spawn_after(Duration::from_millis(10), move || {
+                called.set(true)
+            }));
+        }
+        Delay::new(Duration::from_millis(50)).await.unwrap();
+        assert!(!called.get());
+    }
+
+    #[wasm_bindgen_test]
+    async fn spawn_every_calls_back_until_dropped() {
+        let count = Rc::new(Cell::new(0));
+        let handle = {
+            let count = count.clone();
+            spawn_every(Duration::from_millis(10), move || {
+                count.set(count.get() + 1)
+            })
+        };
+        Delay::new(Duration::from_millis(55)).await.unwrap();
+        assert!(count.get() >= 2);
+        drop(handle);
+        let stopped_at = count.get();
+        Delay::new(Duration::from_millis(50)).await.unwrap();
+        assert_eq!(count.get(), stopped_at);
+    }
+}
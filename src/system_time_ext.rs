@@ -0,0 +1,94 @@
+//! Formatting of `SystemTime` as RFC 3339 timestamps.
+
+use crate::{SystemTime, UNIX_EPOCH};
+
+/// Extension trait for `SystemTime`, to display it in logs.
+pub trait SystemTimeExt {
+    /// Formats this time as an RFC 3339 timestamp in UTC, e.g.
+    /// `2023-11-14T22:13:20Z`.
+    ///
+    /// Milliseconds are appended if there are any, e.g.
+    /// `2023-11-14T22:13:20.250Z`, and finer precision is truncated.
+    fn to_rfc3339(&self) -> String;
+}
+
+impl SystemTimeExt for SystemTime {
+    fn to_rfc3339(&self) -> String {
+        let (secs, millis) = match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_millis()),
+            Err(_) => {
+                let before = UNIX_EPOCH.duration_since(*self).unwrap_or_default();
+                let (secs, millis) = (before.as_secs() as i64, before.subsec_millis());
+                if millis == 0 {
+                    (-secs, 0)
+                } else {
+                    (-secs - 1, 1000 - millis)
+                }
+            }
+        };
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let time = secs.rem_euclid(86400);
+        let mut formatted = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        );
+        if millis != 0 {
+            formatted.push_str(&format!(".{:03}", millis));
+        }
+        formatted.push('Z');
+        formatted
+    }
+}
+
+/// Returns the year, month and day of the given number of days since the UNIX
+/// epoch, in the proleptic Gregorian calendar.
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm, which works on eras
+/// of 400 years.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::SystemTimeExt;
+    use crate::UNIX_EPOCH;
+
+    #[wasm_bindgen_test]
+    fn to_rfc3339() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(UNIX_EPOCH.to_rfc3339(), "1970-01-01T00:00:00Z");
+        assert_eq!(at(1_700_000_000).to_rfc3339(), "2023-11-14T22:13:20Z");
+        assert_eq!(at(951_782_400).to_rfc3339(), "2000-02-29T00:00:00Z");
+        assert_eq!(
+            (at(1) + Duration::from_millis(250)).to_rfc3339(),
+            "1970-01-01T00:00:01.250Z"
+        );
+        assert_eq!(
+            (UNIX_EPOCH - Duration::from_secs(1)).to_rfc3339(),
+            "1969-12-31T23:59:59Z"
+        );
+        assert_eq!(
+            (UNIX_EPOCH - Duration::from_millis(250)).to_rfc3339(),
+            "1969-12-31T23:59:59.750Z"
+        );
+    }
+}
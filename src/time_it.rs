@@ -0,0 +1,65 @@
+//! Measuring how long futures take to complete, for profiling async code
+//! where the usual tools are unavailable.
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::Instant;
+
+/// Runs `future` to completion, and returns its output along with the time
+/// elapsed between its first poll and its completion.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use zduny_wasm_timer::{sleep, time_it};
+///
+/// # async fn example() {
+/// let ((), elapsed) = time_it(sleep(Duration::from_millis(10))).await;
+/// assert!(elapsed >= Duration::from_millis(10));
+/// # }
+/// ```
+pub async fn time_it<F: Future>(future: F) -> (F::Output, Duration) {
+    let start = Instant::now();
+    let output = future.await;
+    (output, start.elapsed())
+}
+
+/// Same as `time_it`, but on WASM also brackets `future` with
+/// `console.time(label)` and `console.timeEnd(label)`, so that the duration
+/// shows up in the browser's developer tools.
+pub async fn time_it_with_label<F: Future>(label: &str, future: F) -> (F::Output, Duration) {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    web_sys::console::time_with_label(label);
+    let result = time_it(future).await;
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    web_sys::console::time_end_with_label(label);
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    let _ = label;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{time_it, time_it_with_label};
+    use crate::sleep_ms;
+
+    #[wasm_bindgen_test]
+    async fn measures_elapsed_time() {
+        let (output, elapsed) = time_it(async {
+            sleep_ms(20).await;
+            42
+        })
+        .await;
+        assert_eq!(output, 42);
+        assert!(elapsed >= Duration::from_millis(20));
+
+        let (output, elapsed) = time_it_with_label("ready", future::ready("done")).await;
+        assert_eq!(output, "done");
+        assert!(elapsed < Duration::from_millis(20));
+    }
+}
@@ -230,14 +230,17 @@
 // limitations under the License.
 
 use crate::Instant;
-use std::cmp::Ordering;
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::io;
+use std::marker::PhantomData;
 use std::mem;
 use std::pin::Pin;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::{Arc, Mutex, Weak};
-use std::task::{Context, Poll};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 
 use futures::prelude::*;
 use futures::task::AtomicWaker;
@@ -245,12 +248,37 @@ use futures::task::AtomicWaker;
 use arc_list::{ArcList, Node};
 use heap::{Heap, Slot};
 
+/// Emits an event at the trace level, through `tracing` with the `tracing`
+/// feature, or else through `log` with the `log` feature. Expands to nothing
+/// without either, in which case the fields aren't evaluated.
+///
+/// Fields are recorded with their `Debug` representation.
+macro_rules! trace_event {
+    ($msg:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($name = ?$value,)* $msg);
+        #[cfg(all(feature = "log", not(feature = "tracing")))]
+        log::trace!(concat!($msg $(, " ", stringify!($name), "={:?}")*) $(, $value)*);
+    };
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod abort;
 mod arc_list;
 mod global;
-mod heap;
+pub mod heap;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod visibility;
 
+pub mod combinators;
+pub use combinators::first_of_with_deadline;
+pub mod error;
+pub use error::{DelayCancelled, Elapsed, TimerDropped, TimerError};
 pub mod ext;
-pub use ext::{TryFutureExt, TryStreamExt};
+pub use ext::{
+    timeout, timeout_at, timeout_reclaim, wall_timeout_at, ScheduledAt, ScheduledAtStream,
+    ScheduledStreamExt, TimeoutReclaim, TimeoutSink, TryFutureExt, TryStreamExt, WallTimeout,
+};
 
 /// A "timer heap" used to power separately owned instances of `Delay` and
 /// `Interval`.
@@ -273,21 +301,71 @@ pub use ext::{TryFutureExt, TryStreamExt};
 /// `Timer` as there is a global one already available for you run on a helper
 /// thread. If this isn't desirable, though, then the
 /// `TimerHandle::set_fallback` method can be used instead!
+///
+/// Embedders with their own main loop can also drive a `Timer` manually,
+/// without relying on the `Future` implementation: create delays through
+/// `Timer::handle`, sleep until `next_deadline`, and call `fire_expired`.
+///
+/// ```
+/// use std::time::Duration;
+/// use zduny_wasm_timer::{Delay, Instant, Timer};
+///
+/// let mut timer = Timer::new();
+/// let deadline = Instant::now() + Duration::from_millis(10);
+/// let delay = Delay::new_handle(deadline, timer.handle());
+/// assert_eq!(timer.next_deadline(), Some(deadline));
+/// assert_eq!(timer.fire_expired(deadline), 1);
+/// # drop(delay);
+/// ```
 pub struct Timer {
     inner: Arc<Inner>,
-    timer_heap: Heap<HeapTimer>,
+    cancellation: Cancellation,
+
+    /// Sequence number of the next entry pushed onto the heap, which orders
+    /// the delays sharing a deadline.
+    next_seq: u64,
+
+    /// Buffer of the updates being processed, kept to reuse its allocation.
+    updates: Vec<Arc<Node<ScheduledTimer>>>,
+
+    /// Buffer of the wakers of the delays being fired, which are woken once
+    /// the heap is unlocked.
+    wakers: Vec<Waker>,
 }
 
+/// Number of the wakers last collected by `Timer::fire` which a new one is
+/// compared to, to skip waking the same task several times.
+const WAKER_DEDUP_WINDOW: usize = 32;
+
 /// A handle to a `Timer` which is used to create instances of a `Delay`.
+///
+/// On WASM with shared memory (the `atomics` target feature), the handles of
+/// the default timer, and the delays and intervals bound to it, must stay on
+/// the thread they were created on, even though they are `Send`: resetting or
+/// dropping them from another thread panics.
 #[derive(Clone)]
 pub struct TimerHandle {
     inner: Weak<Inner>,
 }
 
+mod countdown;
 mod delay;
+mod delay_set;
 mod interval;
-pub use self::delay::Delay;
-pub use self::interval::Interval;
+#[cfg(feature = "mock-clock")]
+mod mock;
+mod ticker;
+pub use self::countdown::CountdownTimer;
+pub use self::delay::{
+    sleep, sleep_micros, sleep_ms, sleep_secs, sleep_until, Delay, DelayHandle, Sleep,
+};
+pub use self::delay_set::DelaySet;
+pub use self::interval::{
+    interval, interval_at, IndexedInterval, Interval, IntervalStream, TriggeredInterval,
+};
+#[cfg(feature = "mock-clock")]
+pub use self::mock::MockClock;
+pub use self::ticker::{Ticker, TickerMode};
 
 struct Inner {
     /// List of updates the `Timer` needs to process
@@ -295,8 +373,36 @@ struct Inner {
 
     /// The blocked `Timer` task to receive notifications to the `list` above.
     waker: AtomicWaker,
+
+    /// Heap of all the timers currently scheduled on the `Timer`. This is
+    /// only modified by the `Timer` itself, but handles may inspect it.
+    timer_heap: Mutex<TimerHeap>,
+
+    /// Number of delays created on the `Timer` so far.
+    total_created: AtomicUsize,
+
+    /// Number of delays the `Timer` fired so far.
+    total_fired: AtomicUsize,
+
+    /// Number of delays dropped or cancelled before firing so far.
+    total_cancelled: AtomicUsize,
+
+    /// Number of entries of `timer_heap` flagged as cancelled, which are yet
+    /// to be discarded. Only modified while `timer_heap` is locked.
+    tombstones: AtomicUsize,
+
+    /// Maximum number of delays fired by a single call to `advance_to` or
+    /// `fire_expired`.
+    max_fires_per_wakeup: AtomicUsize,
+
+    /// Nodes of the delays dropped so far, which new delays reuse rather than
+    /// allocating their own. At most `MAX_FREE_NODES` are kept.
+    free_nodes: Mutex<Vec<Arc<Node<ScheduledTimer>>>>,
 }
 
+/// Maximum number of nodes kept by a `Timer` for reuse.
+const MAX_FREE_NODES: usize = 1024;
+
 /// Shared state between the `Timer` and a `Delay`.
 struct ScheduledTimer {
     waker: AtomicWaker,
@@ -310,17 +416,77 @@ struct ScheduledTimer {
     inner: Weak<Inner>,
     at: Mutex<Option<Instant>>,
 
+    /// When the timer last fired this delay, as seen by the timer during the
+    /// wake-up in which it fired.
+    fired_at: Mutex<Option<Instant>>,
+
+    /// Identifier of the delay in events, unique within the process.
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    id: u64,
+
     // TODO: this is only accessed by the timer thread, should have a more
     // lightweight protection than a `Mutex`
     slot: Mutex<Option<Slot>>,
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    hint: Mutex<SchedulerHint>,
 }
 
-/// Entries in the timer heap, sorted by the instant they're firing at and then
-/// also containing some payload data.
+/// Hint about how the WASM timer should schedule its wake-up when a `Delay` is
+/// the next one to fire.
+///
+/// Browsers clamp nested `setTimeout` calls to at least 4 milliseconds, so by
+/// default deadlines closer than that are waited for by posting messages on a
+/// `MessageChannel` instead. This hint has no effect on other targets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchedulerHint {
+    /// Use `setTimeout`, or a `MessageChannel` for deadlines closer than
+    /// 4 milliseconds.
+    #[default]
+    Auto,
+    /// Always use `setTimeout`, which is cheaper but may fire a few
+    /// milliseconds late.
+    Coarse,
+    /// Always use a `MessageChannel`, which repeatedly yields to the event
+    /// loop until the deadline is reached. This is the most precise, but also
+    /// keeps the browser busy while waiting.
+    Precise,
+    /// Check the deadline from a `requestAnimationFrame` loop, so that the
+    /// delay completes right before the browser paints. The loop only runs
+    /// while such a delay is the next one to fire, and `setTimeout` is used
+    /// instead while the page is hidden, as hidden pages don't get frames.
+    AnimationFrame,
+}
+
+/// How a `Timer` gets rid of the delays which are dropped before firing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cancellation {
+    /// Remove the delay from the heap as soon as the `Timer` processes its
+    /// drop, in O(log n).
+    #[default]
+    Eager,
+    /// Only flag the entry of the delay in the heap, in O(1). Flagged entries
+    /// are discarded when they reach the top of the heap, or all at once when
+    /// they make up more than half of the heap.
+    ///
+    /// This is cheaper when most delays are dropped before firing, e.g. for
+    /// request timeouts, at the cost of keeping their entries around for a
+    /// while.
+    Lazy,
+}
+
+/// Heap of the scheduled timers, keyed by the instant they're firing at and
+/// then by their sequence number.
+type TimerHeap = Heap<(Instant, u64), HeapTimer>;
+
+/// Payload of the entries of the timer heap.
 struct HeapTimer {
-    at: Instant,
     gen: usize,
     node: Arc<Node<ScheduledTimer>>,
+
+    // Whether the delay was dropped, with `Cancellation::Lazy`. Such entries
+    // are never fired.
+    cancelled: Cell<bool>,
 }
 
 impl Timer {
@@ -330,11 +496,43 @@ impl Timer {
             inner: Arc::new(Inner {
                 list: ArcList::new(),
                 waker: AtomicWaker::new(),
+                timer_heap: Mutex::new(Heap::new()),
+                total_created: AtomicUsize::new(0),
+                total_fired: AtomicUsize::new(0),
+                total_cancelled: AtomicUsize::new(0),
+                tombstones: AtomicUsize::new(0),
+                max_fires_per_wakeup: AtomicUsize::new(usize::MAX),
+                free_nodes: Mutex::new(Vec::new()),
             }),
-            timer_heap: Heap::new(),
+            cancellation: Cancellation::default(),
+            next_seq: 0,
+            updates: Vec::new(),
+            wakers: Vec::new(),
         }
     }
 
+    /// Sets how this timer gets rid of the delays which are dropped before
+    /// firing. See `Cancellation` for details.
+    pub fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.cancellation = cancellation;
+    }
+
+    /// Sets the maximum number of delays completed by a single call to
+    /// `advance_to` or `fire_expired`. There is no limit by default.
+    ///
+    /// Delays which are due but over the limit are completed by the next
+    /// calls, still in order of deadline, and then in the order they were
+    /// scheduled in. On WASM, the global timer yields back to the browser
+    /// between batches, so that a burst of expired delays, e.g. after a stall
+    /// of a background tab, doesn't make for a long task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn set_max_fires_per_wakeup(&mut self, n: usize) {
+        self.handle().set_max_fires_per_wakeup(n);
+    }
+
     /// Returns a handle to this timer heap, used to create new timeouts.
     pub fn handle(&self) -> TimerHandle {
         TimerHandle {
@@ -348,7 +546,60 @@ impl Timer {
     /// Event loops or threads typically want to sleep until the specified
     /// instant.
     pub fn next_event(&self) -> Option<Instant> {
-        self.timer_heap.peek().map(|t| t.at)
+        self.inner.next_deadline()
+    }
+
+    /// Returns the instant at which the soonest delay of this timer fires, or
+    /// `None` if there is none.
+    ///
+    /// Contrary to `next_event`, delays which were created, reset or dropped
+    /// since the timer was last polled are taken into account.
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        self.process_updates();
+        self.next_event()
+    }
+
+    /// Completes every delay whose deadline is at or before `now`, and returns
+    /// how many of them were completed.
+    ///
+    /// Delays which were created, reset or dropped since the timer was last
+    /// polled are taken into account, so this is all a custom event loop needs
+    /// to call to drive the timer. At most as many delays as set with
+    /// `set_max_fires_per_wakeup` are completed, the others being left for
+    /// the next call.
+    pub fn fire_expired(&mut self, now: Instant) -> usize {
+        self.process_updates();
+        self.fire(now)
+    }
+
+    /// Runs a single step of this timer as if the current instant was `now`:
+    /// like `fire_expired`, and also registers `cx` to be notified when delays
+    /// are created, reset or dropped, like polling the timer does.
+    ///
+    /// Returns `Poll::Ready(())` once no delay is left, and `Poll::Pending`
+    /// otherwise, `next_deadline` telling when the next step is due.
+    ///
+    /// Stepping through the deadlines rather than waiting for them makes for
+    /// deterministic tests of timer-based code, on any target. Only the delays
+    /// bound to this timer are driven, e.g. through `TimerHandle::set_default`.
+    pub fn poll_at(&mut self, now: Instant, cx: &mut Context<'_>) -> Poll<()> {
+        self.inner.waker.register(cx.waker());
+        self.fire_expired(now);
+        match self.next_event() {
+            Some(_) => Poll::Pending,
+            None => Poll::Ready(()),
+        }
+    }
+
+    /// Same as `next_event`, but also returns the scheduling hint of the timer
+    /// firing next.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub(crate) fn next_event_with_hint(&self) -> Option<(Instant, SchedulerHint)> {
+        let mut timer_heap = lock(&self.inner.timer_heap);
+        self.inner.discard_cancelled(&mut timer_heap);
+        timer_heap
+            .peek()
+            .map(|(&(at, _), t)| (at, *lock(&t.node.hint)))
     }
 
     /// Proces any timers which are supposed to fire at or before the current
@@ -364,27 +615,85 @@ impl Timer {
     /// This method should be called on `Timer` periodically to advance the
     /// internal state and process any pending timers which need to fire.
     pub fn advance_to(&mut self, now: Instant) {
-        loop {
-            match self.timer_heap.peek() {
-                Some(head) if head.at <= now => {}
+        self.fire(now);
+    }
+
+    /// Fires the timers of the heap which are due at `now`, up to the limit
+    /// set with `set_max_fires_per_wakeup`, and returns how many of them were
+    /// fired.
+    fn fire(&mut self, now: Instant) -> usize {
+        let max_fires = self.inner.max_fires_per_wakeup.load(SeqCst);
+        let mut fired = 0;
+        let mut wakers = mem::take(&mut self.wakers);
+        let mut timer_heap = lock(&self.inner.timer_heap);
+        while fired < max_fires {
+            self.inner.discard_cancelled(&mut timer_heap);
+            match timer_heap.peek() {
+                Some((&(at, _), _)) if at <= now => {}
                 Some(_) => break,
                 None => break,
             };
 
-            // Flag the timer as fired and then notify its task, if any, that's
-            // blocked.
-            let heap_timer = self.timer_heap.pop().unwrap();
-            *heap_timer.node.slot.lock().unwrap() = None;
+            // Flag the timer as fired, and collect the waker of its task, if
+            // any, to notify it once the heap is unlocked.
+            // The deadline is only used to trace the event.
+            #[allow(unused_variables)]
+            let ((at, _), heap_timer) = timer_heap.pop().unwrap();
+            *lock(&heap_timer.node.slot) = None;
             let bits = heap_timer.gen << 2;
+            *lock(&heap_timer.node.fired_at) = Some(now);
             match heap_timer
                 .node
                 .state
                 .compare_exchange(bits, bits | 0b01, SeqCst, SeqCst)
             {
-                Ok(_) => heap_timer.node.waker.wake(),
+                Ok(_) => {
+                    trace_event!(
+                        "delay fired",
+                        delay_id = heap_timer.node.id,
+                        deadline = at,
+                        lateness = lateness(at, now),
+                    );
+                    if let Some(waker) = heap_timer.node.waker.take() {
+                        // Delays sharing a deadline often belong to the same
+                        // task, which only needs to be woken once.
+                        let mut recent = wakers.iter().rev().take(WAKER_DEDUP_WINDOW);
+                        if !recent.any(|w| w.will_wake(&waker)) {
+                            wakers.push(waker);
+                        }
+                    }
+                    self.inner.total_fired.fetch_add(1, SeqCst);
+                    fired += 1;
+                }
                 Err(_b) => {}
             }
         }
+        drop(timer_heap);
+        for waker in wakers.drain(..) {
+            waker.wake();
+        }
+        self.wakers = wakers;
+        fired
+    }
+
+    /// Applies the updates pushed by delays since the last time they were
+    /// processed.
+    fn process_updates(&mut self) {
+        // The list pops the most recent update first, but delays sharing a
+        // deadline are fired in the order they were scheduled in.
+        let mut updates = mem::take(&mut self.updates);
+        updates.extend(self.inner.list.take().drain());
+        for node in updates.drain(..).rev() {
+            let at = *lock(&node.at);
+            match at {
+                Some(at) => self.update_or_add(at, node),
+                None => {
+                    self.remove(node.clone());
+                    self.inner.recycle(node);
+                }
+            }
+        }
+        self.updates = updates;
     }
 
     /// Either updates the timer at slot `idx` to fire at `at`, or adds a new
@@ -394,29 +703,55 @@ impl Timer {
         // In theory we could update it in place and then do the percolation
         // as necessary
         let gen = node.state.load(SeqCst) >> 2;
-        let mut slot = node.slot.lock().unwrap();
+        let mut timer_heap = lock(&self.inner.timer_heap);
+        let mut slot = lock(&node.slot);
         if let Some(heap_slot) = slot.take() {
-            self.timer_heap.remove(heap_slot);
+            timer_heap.remove(heap_slot);
         }
-        *slot = Some(self.timer_heap.push(HeapTimer {
-            at,
-            gen,
-            node: node.clone(),
-        }));
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let heap_slot = timer_heap.push(
+            (at, seq),
+            HeapTimer {
+                gen,
+                node: node.clone(),
+                cancelled: Cell::new(false),
+            },
+        );
+        trace_event!("delay armed", delay_id = node.id, deadline = at);
+        *slot = Some(heap_slot);
     }
 
     fn remove(&mut self, node: Arc<Node<ScheduledTimer>>) {
         // If this `idx` is still around and it's still got a registered timer,
         // then we jettison it form the timer heap.
-        let mut slot = node.slot.lock().unwrap();
+        let mut slot = lock(&node.slot);
         let heap_slot = match slot.take() {
             Some(slot) => slot,
             None => return,
         };
-        self.timer_heap.remove(heap_slot);
+        let mut timer_heap = lock(&self.inner.timer_heap);
+        trace_event!(
+            "delay cancelled",
+            delay_id = node.id,
+            deadline = timer_heap.get(&heap_slot).0 .0,
+        );
+        match self.cancellation {
+            Cancellation::Eager => {
+                timer_heap.remove(heap_slot);
+            }
+            Cancellation::Lazy => {
+                timer_heap.get(&heap_slot).1.cancelled.set(true);
+                let tombstones = self.inner.tombstones.fetch_add(1, SeqCst) + 1;
+                if tombstones > timer_heap.len() / 2 {
+                    timer_heap.retain(|_, t| !t.cancelled.get());
+                    self.inner.tombstones.store(0, SeqCst);
+                }
+            }
+        }
     }
 
-    fn invalidate(&mut self, node: Arc<Node<ScheduledTimer>>) {
+    fn invalidate(node: Arc<Node<ScheduledTimer>>) {
         node.state.fetch_or(0b10, SeqCst);
         node.waker.wake();
     }
@@ -433,20 +768,16 @@ impl Future for Timer {
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         Pin::new(&mut self.inner).waker.register(cx.waker());
-        let mut list = self.inner.list.take();
-        while let Some(node) = list.pop() {
-            let at = *node.at.lock().unwrap();
-            match at {
-                Some(at) => self.update_or_add(at, node),
-                None => self.remove(node),
-            }
-        }
+        self.process_updates();
         Poll::Pending
     }
 }
 
 impl Drop for Timer {
     fn drop(&mut self) {
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        visibility::untrack(&self.inner);
+
         // Seal off our list to prevent any more updates from getting pushed on.
         // Any timer which sees an error from the push will immediately become
         // inert.
@@ -455,11 +786,12 @@ impl Drop for Timer {
         // Now that we'll never receive another timer, drain the list of all
         // updates and also drain our heap of all active timers, invalidating
         // everything.
-        while let Some(t) = list.pop() {
-            self.invalidate(t);
+        for t in list.drain() {
+            Timer::invalidate(t);
         }
-        while let Some(t) = self.timer_heap.pop() {
-            self.invalidate(t.node);
+        let mut timer_heap = lock(&self.inner.timer_heap);
+        while let Some((_, t)) = timer_heap.pop() {
+            Timer::invalidate(t.node);
         }
     }
 }
@@ -470,28 +802,194 @@ impl fmt::Debug for Timer {
     }
 }
 
-impl PartialEq for HeapTimer {
-    fn eq(&self, other: &HeapTimer) -> bool {
-        self.at == other.at
+impl Inner {
+    /// Returns a node holding `data`, reusing the one of a dropped delay if
+    /// there's any.
+    fn node(&self, data: ScheduledTimer) -> Arc<Node<ScheduledTimer>> {
+        let free = lock(&self.free_nodes).pop();
+        if let Some(mut node) = free {
+            if let Some(node_mut) = Arc::get_mut(&mut node) {
+                **node_mut = data;
+                return node;
+            }
+        }
+        Arc::new(Node::new(data))
+    }
+
+    /// Keeps `node` for reuse if nothing else references it anymore, i.e. its
+    /// delay is gone and the heap doesn't hold it.
+    fn recycle(&self, node: Arc<Node<ScheduledTimer>>) {
+        if Arc::strong_count(&node) != 1 {
+            return;
+        }
+        let mut free_nodes = lock(&self.free_nodes);
+        if free_nodes.len() < MAX_FREE_NODES {
+            free_nodes.push(node);
+        }
+    }
+
+    fn active_count(&self) -> usize {
+        let timer_heap = lock(&self.timer_heap);
+        timer_heap.len() - self.tombstones.load(SeqCst)
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        let mut timer_heap = lock(&self.timer_heap);
+        self.discard_cancelled(&mut timer_heap);
+        timer_heap.peek().map(|(&(at, _), _)| at)
     }
+
+    /// Pops the entries flagged as cancelled off the top of the heap, so that
+    /// its minimum is a live delay.
+    fn discard_cancelled(&self, timer_heap: &mut TimerHeap) {
+        while timer_heap.peek().is_some_and(|(_, t)| t.cancelled.get()) {
+            timer_heap.pop();
+            self.tombstones.fetch_sub(1, SeqCst);
+        }
+    }
+
+    /// Invalidates every timer, both the ones in the heap and the ones with
+    /// pending updates, and returns how many timers were in the heap.
+    fn cancel_all(&self) -> usize {
+        for node in self.list.take().drain() {
+            Timer::invalidate(node);
+        }
+        let mut timer_heap = lock(&self.timer_heap);
+        let count = timer_heap.len() - self.tombstones.swap(0, SeqCst);
+        while let Some((_, t)) = timer_heap.pop() {
+            if t.cancelled.get() {
+                continue;
+            }
+            *lock(&t.node.slot) = None;
+            Timer::invalidate(t.node);
+        }
+        drop(timer_heap);
+        trace_event!("all delays cancelled", count = count);
+        self.total_cancelled.fetch_add(count, SeqCst);
+        // Let whatever drives the timer know that it has nothing left to wait for.
+        self.waker.wake();
+        count
+    }
+}
+
+/// Returns the current instant according to the clock driving `Delay`s.
+///
+/// This is the instant of the `MockClock` installed on the current thread, if
+/// any.
+pub(crate) fn now() -> Instant {
+    #[cfg(feature = "mock-clock")]
+    if let Some(now) = mock::now() {
+        return now;
+    }
+    clock_now()
+}
+
+#[cfg(not(all(
+    feature = "tokio",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+)))]
+fn clock_now() -> Instant {
+    Instant::now()
+}
+
+/// With the `tokio` feature this is tokio's clock, which may be paused or
+/// advanced manually in tests.
+#[cfg(all(
+    feature = "tokio",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+fn clock_now() -> Instant {
+    tokio::time::Instant::now().into_std()
 }
 
-impl Eq for HeapTimer {}
+/// Locks `mutex`, even if a thread panicked while holding it.
+///
+/// A panic while one of the timer's locks is held must not make every later
+/// timer operation panic too, so poisoning is ignored.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
-impl PartialOrd for HeapTimer {
-    fn partial_cmp(&self, other: &HeapTimer) -> Option<Ordering> {
-        Some(self.cmp(other))
+/// Returns how long after `deadline` something happening at `at` is, or zero
+/// if it's not after it.
+pub(crate) fn lateness(deadline: Instant, at: Instant) -> Duration {
+    if at > deadline {
+        at - deadline
+    } else {
+        Duration::ZERO
     }
 }
 
-impl Ord for HeapTimer {
-    fn cmp(&self, other: &HeapTimer) -> Ordering {
-        self.at.cmp(&other.at)
+/// Returns `at + dur`, or the latest instant that can be represented if that
+/// overflows, so that deadlines computed from
+/// arbitrary durations never panic.
+///
+/// Clamping is traced, as it usually means a duration is bogus.
+pub(crate) fn saturating_add(at: Instant, dur: Duration) -> Instant {
+    if let Some(new) = at.checked_add(dur) {
+        return new;
+    }
+    trace_event!("deadline saturated", instant = at, duration = dur);
+    // Binary search on the number of nanoseconds that can still be added.
+    let (mut lo, mut hi) = (0, dur.as_nanos());
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if at.checked_add(nanos_to_duration(mid)).is_some() {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    at + nanos_to_duration(lo)
+}
+
+/// Inverse of `Duration::as_nanos`, saturating at `Duration::MAX` for values
+/// which don't fit in a `Duration`.
+pub(crate) fn nanos_to_duration(nanos: u128) -> Duration {
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(secs) => Duration::new(secs, (nanos % 1_000_000_000) as u32),
+        Err(_) => Duration::MAX,
     }
 }
 
 static HANDLE_FALLBACK: AtomicUsize = AtomicUsize::new(0);
 
+#[cfg(all(
+    target_arch = "wasm32",
+    target_os = "unknown",
+    target_feature = "atomics"
+))]
+thread_local! {
+    /// With shared memory, each thread has its own global fallback handle.
+    static THREAD_FALLBACK: RefCell<Option<TimerHandle>> = const { RefCell::new(None) };
+}
+
+thread_local! {
+    /// Stack of the handles installed with `TimerHandle::set_default`.
+    static SCOPED_DEFAULT: RefCell<Vec<TimerHandle>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Statistics about a `Timer`, returned by `TimerHandle::stats`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimerStats {
+    /// Number of delays currently scheduled. Delays created or reset since
+    /// the `Timer` was last polled aren't accounted for yet.
+    pub pending: usize,
+    /// Approximate number of delays created, reset or dropped which the
+    /// `Timer` has yet to process.
+    pub queued_updates: usize,
+    /// Instant at which the soonest scheduled delay fires.
+    pub next_deadline: Option<Instant>,
+    /// Number of delays created on the `Timer` so far.
+    pub total_created: usize,
+    /// Number of delays fired so far. A delay which is reset and fires again
+    /// counts several times.
+    pub total_fired: usize,
+    /// Number of delays dropped before firing, or cancelled with
+    /// `TimerHandle::cancel_all`, so far.
+    pub total_cancelled: usize,
+}
+
 /// Error returned from `TimerHandle::set_fallback`.
 #[derive(Clone, Debug)]
 pub struct SetDefaultError(());
@@ -532,6 +1030,150 @@ impl TimerHandle {
         }
     }
 
+    /// Installs this timer handle as the default for the current thread, until
+    /// the returned guard is dropped.
+    ///
+    /// While the guard is alive, `TimerHandle::default`, and therefore
+    /// `Delay::new` and friends, return this handle instead of the global
+    /// fallback. This makes it possible to run isolated timers side by side,
+    /// for example in tests. With the `tokio` feature, delays created while a
+    /// guard is alive are bound to this handle rather than to tokio.
+    ///
+    /// Guards can be nested, and dropping one restores the default that was
+    /// installed before it.
+    ///
+    /// # Panics
+    ///
+    /// Dropping a guard while a guard created after it is still alive panics,
+    /// as the defaults couldn't be restored in a meaningful order.
+    pub fn set_default(&self) -> DefaultGuard {
+        let depth = SCOPED_DEFAULT.with(|scoped| {
+            let mut scoped = scoped.borrow_mut();
+            scoped.push(self.clone());
+            scoped.len()
+        });
+        DefaultGuard {
+            depth,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Creates a `Delay` which fires `dur` from now on the `Timer` this handle
+    /// refers to.
+    ///
+    /// Handles can be cloned and sent to other threads or tasks, which can
+    /// then create delays on the same `Timer` without going through the
+    /// default one.
+    pub fn delay(&self, dur: Duration) -> Delay {
+        self.delay_at(saturating_add(now(), dur))
+    }
+
+    /// Creates a `Delay` which fires at `at` on the `Timer` this handle refers
+    /// to.
+    pub fn delay_at(&self, at: Instant) -> Delay {
+        Delay::new_handle(at, self.clone())
+    }
+
+    /// Creates an `Interval` which first fires `period` from now, and then
+    /// every `period`, on the `Timer` this handle refers to.
+    pub fn interval(&self, period: Duration) -> Interval {
+        Interval::new_handle(saturating_add(now(), period), period, self.clone())
+    }
+
+    /// Returns the handle installed on this thread with `set_default`, if any.
+    pub(crate) fn scoped_default() -> Option<TimerHandle> {
+        SCOPED_DEFAULT.with(|scoped| scoped.borrow().last().cloned())
+    }
+
+    /// Returns the number of timers currently scheduled on the `Timer` this
+    /// handle refers to.
+    ///
+    /// Timers which were created or reset since the last time the `Timer`
+    /// was polled aren't accounted for yet. Returns 0 if the `Timer` is gone.
+    pub fn active_count(&self) -> usize {
+        self.inner.upgrade().map_or(0, |inner| inner.active_count())
+    }
+
+    /// Returns the instant at which the soonest timer scheduled on the `Timer`
+    /// this handle refers to will fire, or `None` if there is no such timer
+    /// or the `Timer` is gone.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.inner.upgrade().and_then(|inner| inner.next_deadline())
+    }
+
+    /// Cancels every delay of the `Timer` this handle refers to, and returns
+    /// how many were scheduled.
+    ///
+    /// Cancelled delays resolve to an error, as if the `Timer` was gone, and
+    /// can't be reset. The `Timer` itself can still be used to create new
+    /// delays.
+    pub fn cancel_all(&self) -> usize {
+        self.inner.upgrade().map_or(0, |inner| inner.cancel_all())
+    }
+
+    /// Same as `Timer::set_max_fires_per_wakeup`, for the `Timer` this handle
+    /// refers to. Does nothing if the `Timer` is gone.
+    ///
+    /// This is how the limit of the global timer is set, through
+    /// `TimerHandle::default()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn set_max_fires_per_wakeup(&self, n: usize) {
+        assert!(
+            n > 0,
+            "the maximum number of fires per wake-up must be positive"
+        );
+        if let Some(inner) = self.inner.upgrade() {
+            inner.max_fires_per_wakeup.store(n, SeqCst);
+        }
+    }
+
+    /// Returns statistics about the `Timer` this handle refers to, or `None`
+    /// if the `Timer` is gone.
+    ///
+    /// This is safe to call at any time, including while the `Timer` is
+    /// firing delays.
+    pub fn stats(&self) -> Option<TimerStats> {
+        let inner = self.inner.upgrade()?;
+        let (pending, next_deadline) = {
+            let mut timer_heap = lock(&inner.timer_heap);
+            inner.discard_cancelled(&mut timer_heap);
+            (
+                timer_heap.len() - inner.tombstones.load(SeqCst),
+                timer_heap.peek().map(|(&(at, _), _)| at),
+            )
+        };
+        Some(TimerStats {
+            pending,
+            queued_updates: inner.list.len(),
+            next_deadline,
+            total_created: inner.total_created.load(SeqCst),
+            total_fired: inner.total_fired.load(SeqCst),
+            total_cancelled: inner.total_cancelled.load(SeqCst),
+        })
+    }
+
+    /// Makes the `Timer` this handle refers to catch up as soon as the page
+    /// becomes visible again.
+    ///
+    /// Browsers throttle the timers of background tabs, so that `Delay`s and
+    /// `Interval`s drift while the page is hidden. Once this is called, the
+    /// `Timer` fires everything that is overdue and re-arms itself precisely
+    /// as soon as the page is visible again. While the page is hidden, the
+    /// global timer also stops scheduling wake-ups more precisely than
+    /// `setTimeout`, as they would be throttled anyway.
+    ///
+    /// The listener is removed when the `Timer` is dropped. Does nothing
+    /// outside of a document, e.g. in workers, or if the `Timer` is gone.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn track_visibility(&self) {
+        if let Some(inner) = self.inner.upgrade() {
+            visibility::track(&inner);
+        }
+    }
+
     fn into_usize(self) -> usize {
         unsafe { mem::transmute::<Weak<Inner>, usize>(self.inner) }
     }
@@ -543,20 +1185,39 @@ impl TimerHandle {
 }
 
 impl Default for TimerHandle {
-    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    /// Returns the handle installed on this thread with `set_default`, if any,
+    /// or else the global fallback handle.
     fn default() -> TimerHandle {
+        TimerHandle::scoped_default().unwrap_or_else(TimerHandle::global_default)
+    }
+}
+
+impl TimerHandle {
+    /// Same as `TimerHandle::default`, but fails if the global fallback timer
+    /// couldn't be started, e.g. because its helper thread couldn't be
+    /// spawned, rather than returning a handle whose delays all fail.
+    pub fn try_default() -> io::Result<TimerHandle> {
+        match TimerHandle::scoped_default() {
+            Some(handle) => Ok(handle),
+            None => TimerHandle::try_global_default(),
+        }
+    }
+
+    /// If the helper thread can't be created, returns a "defunkt" handle which
+    /// will return errors when timer objects are attempted to be associated.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn global_default() -> TimerHandle {
+        TimerHandle::try_global_default().unwrap_or_else(|_| TimerHandle { inner: Weak::new() })
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    fn try_global_default() -> io::Result<TimerHandle> {
         let mut fallback = HANDLE_FALLBACK.load(SeqCst);
 
         // If the fallback hasn't been previously initialized then let's spin
-        // up a helper thread and try to initialize with that. If we can't
-        // actually create a helper thread then we'll just return a "defunkt"
-        // handle which will return errors when timer objects are attempted to
-        // be associated.
+        // up a helper thread and try to initialize with that.
         if fallback == 0 {
-            let helper = match global::HelperThread::new() {
-                Ok(helper) => helper,
-                Err(_) => return TimerHandle { inner: Weak::new() },
-            };
+            let helper = global::HelperThread::new()?;
 
             // If we successfully set ourselves as the actual fallback then we
             // want to `forget` the helper thread to ensure that it persists
@@ -568,7 +1229,7 @@ impl Default for TimerHandle {
             if helper.handle().set_as_global_fallback().is_ok() {
                 let ret = helper.handle();
                 helper.forget();
-                return ret;
+                return Ok(ret);
             }
             fallback = HANDLE_FALLBACK.load(SeqCst);
         }
@@ -580,13 +1241,23 @@ impl Default for TimerHandle {
         unsafe {
             let handle = TimerHandle::from_usize(fallback);
             let ret = handle.clone();
-            drop(handle.into_usize());
-            return ret;
+            let _ = handle.into_usize();
+            Ok(ret)
         }
     }
 
+    /// Starting the timer of the event loop can't fail.
     #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
-    fn default() -> TimerHandle {
+    fn try_global_default() -> io::Result<TimerHandle> {
+        Ok(TimerHandle::global_default())
+    }
+
+    #[cfg(all(
+        target_arch = "wasm32",
+        target_os = "unknown",
+        not(target_feature = "atomics")
+    ))]
+    fn global_default() -> TimerHandle {
         let mut fallback = HANDLE_FALLBACK.load(SeqCst);
 
         // If the fallback hasn't been previously initialized then let's spin
@@ -621,6 +1292,179 @@ impl Default for TimerHandle {
             ret
         }
     }
+
+    /// With shared memory, each thread has its own event loop and needs its
+    /// own timer driven by it, so the fallback is per-thread and
+    /// `set_as_global_fallback` has no effect.
+    ///
+    /// The handle, and the delays created with it, must not be used from
+    /// other threads: the timer can only be woken up from its own thread, and
+    /// panics otherwise.
+    #[cfg(all(
+        target_arch = "wasm32",
+        target_os = "unknown",
+        target_feature = "atomics"
+    ))]
+    fn global_default() -> TimerHandle {
+        THREAD_FALLBACK.with(|fallback| {
+            fallback
+                .borrow_mut()
+                .get_or_insert_with(global::run)
+                .clone()
+        })
+    }
+
+    /// Returns the global fallback handle, without starting the global timer
+    /// if it isn't running yet.
+    #[cfg(not(all(
+        target_arch = "wasm32",
+        target_os = "unknown",
+        target_feature = "atomics"
+    )))]
+    fn global_fallback() -> Option<TimerHandle> {
+        let fallback = HANDLE_FALLBACK.load(SeqCst);
+        if fallback == 0 {
+            return None;
+        }
+        unsafe {
+            let handle = TimerHandle::from_usize(fallback);
+            let ret = handle.clone();
+            let _ = handle.into_usize();
+            Some(ret)
+        }
+    }
+
+    /// Returns the global fallback handle of this thread.
+    #[cfg(all(
+        target_arch = "wasm32",
+        target_os = "unknown",
+        target_feature = "atomics"
+    ))]
+    fn global_fallback() -> Option<TimerHandle> {
+        THREAD_FALLBACK.with(|fallback| fallback.borrow().clone())
+    }
+
+    /// Forgets the global fallback handle if its `Timer` is gone, so that the
+    /// next call to `TimerHandle::default` starts a new one.
+    #[cfg(all(
+        target_arch = "wasm32",
+        target_os = "unknown",
+        not(target_feature = "atomics")
+    ))]
+    fn reset_dead_global_fallback() {
+        let fallback = HANDLE_FALLBACK.load(SeqCst);
+        if fallback == 0 {
+            return;
+        }
+        unsafe {
+            let handle = TimerHandle::from_usize(fallback);
+            if handle.inner.strong_count() == 0
+                && HANDLE_FALLBACK
+                    .compare_exchange(fallback, 0, SeqCst, SeqCst)
+                    .is_ok()
+            {
+                drop(handle);
+            } else {
+                let _ = handle.into_usize();
+            }
+        }
+    }
+
+    /// Forgets the global fallback handle of this thread if its `Timer` is
+    /// gone, so that the next call to `TimerHandle::default` starts a new one.
+    #[cfg(all(
+        target_arch = "wasm32",
+        target_os = "unknown",
+        target_feature = "atomics"
+    ))]
+    fn reset_dead_global_fallback() {
+        THREAD_FALLBACK.with(|fallback| {
+            let mut fallback = fallback.borrow_mut();
+            if fallback
+                .as_ref()
+                .is_some_and(|h| h.inner.strong_count() == 0)
+            {
+                *fallback = None;
+            }
+        })
+    }
+}
+
+/// Application-level management of the global timer, i.e. the one used by
+/// `Delay::new` and friends unless another handle is installed.
+///
+/// This is typically used on teardown, e.g. when a single-page application
+/// navigates away, so that no timer fires after the components using it
+/// are gone. None of these functions start the global timer if it isn't
+/// running yet.
+#[derive(Debug)]
+pub struct GlobalTimerHandle(());
+
+impl GlobalTimerHandle {
+    /// Cancels every pending delay of the global timer, and returns how many
+    /// were scheduled.
+    ///
+    /// Cancelled delays resolve to an error. On WASM, the pending JavaScript
+    /// timeout of the global timer is cleared as well.
+    pub fn cancel_all() -> usize {
+        TimerHandle::global_fallback().map_or(0, |handle| handle.cancel_all())
+    }
+
+    /// Returns the number of delays currently scheduled on the global timer.
+    pub fn pending_count() -> usize {
+        TimerHandle::global_fallback().map_or(0, |handle| handle.active_count())
+    }
+
+    /// Returns the instant at which the soonest delay of the global timer
+    /// fires, if any.
+    pub fn next_expiry() -> Option<Instant> {
+        TimerHandle::global_fallback().and_then(|handle| handle.next_deadline())
+    }
+
+    /// Shuts down the global timer started by this crate, if it is running.
+    ///
+    /// Its pending JavaScript timeout is cleared and its callbacks are
+    /// released, and all of its delays fail with `TimerDropped`. Wake-ups
+    /// which were already queued become no-ops. The next delay created
+    /// through the global timer starts a new one. A handle installed with
+    /// `TimerHandle::set_as_global_fallback` is not affected.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn shutdown() {
+        global::shutdown();
+        TimerHandle::reset_dead_global_fallback();
+    }
+}
+
+/// Guard returned by `TimerHandle::set_default`, which restores the previous
+/// default timer handle of the thread when dropped.
+#[must_use = "the default is restored as soon as the guard is dropped"]
+pub struct DefaultGuard {
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for DefaultGuard {
+    fn drop(&mut self) {
+        let in_order = SCOPED_DEFAULT.with(|scoped| {
+            let mut scoped = scoped.borrow_mut();
+            if scoped.len() != self.depth {
+                return false;
+            }
+            scoped.pop();
+            true
+        });
+        if !in_order && !std::thread::panicking() {
+            panic!("DefaultGuard dropped out of order");
+        }
+    }
+}
+
+impl fmt::Debug for DefaultGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("DefaultGuard")
+            .field("depth", &self.depth)
+            .finish()
+    }
 }
 
 impl fmt::Debug for TimerHandle {
@@ -630,3 +1474,587 @@ impl fmt::Debug for TimerHandle {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::task::noop_waker;
+    use futures::Future;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{Cancellation, Delay, Instant, Interval, Timer};
+
+    fn poll_timer(timer: &mut Timer) {
+        let waker = noop_waker();
+        let _ = Pin::new(timer).poll(&mut Context::from_waker(&waker));
+    }
+
+    #[wasm_bindgen_test]
+    fn active_count_and_next_deadline() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        assert_eq!(handle.active_count(), 0);
+        assert_eq!(handle.next_deadline(), None);
+
+        let now = Instant::now();
+        let soonest = now + Duration::from_secs(5);
+        let _a = Delay::new_handle(now + Duration::from_secs(30), handle.clone());
+        let _b = Delay::new_handle(soonest, handle.clone());
+        let c = Delay::new_handle(now + Duration::from_secs(10), handle.clone());
+        poll_timer(&mut timer);
+        assert_eq!(handle.active_count(), 3);
+        assert_eq!(handle.next_deadline(), Some(soonest));
+
+        drop(c);
+        poll_timer(&mut timer);
+        assert_eq!(handle.active_count(), 2);
+        assert_eq!(handle.next_deadline(), Some(soonest));
+
+        timer.advance_to(soonest);
+        assert_eq!(handle.active_count(), 1);
+        assert_eq!(handle.next_deadline(), Some(now + Duration::from_secs(30)));
+
+        drop(timer);
+        assert_eq!(handle.active_count(), 0);
+        assert_eq!(handle.next_deadline(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn manual_driving() {
+        let mut timer = Timer::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now() + Duration::from_secs(3600);
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut a = Delay::new_handle(at(1), timer.handle());
+        let mut b = Delay::new_handle(at(2), timer.handle());
+        let mut c = Delay::new_handle(at(2), timer.handle());
+        assert_eq!(timer.next_deadline(), Some(at(1)));
+        assert!(Pin::new(&mut a).poll(&mut cx).is_pending());
+
+        assert_eq!(timer.fire_expired(at(0)), 0);
+        assert!(Pin::new(&mut a).poll(&mut cx).is_pending());
+
+        assert_eq!(timer.fire_expired(at(1)), 1);
+        assert!(Pin::new(&mut a).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut b).poll(&mut cx).is_pending());
+        assert_eq!(timer.next_deadline(), Some(at(2)));
+
+        c.reset_at(at(3));
+        assert_eq!(timer.fire_expired(at(2)), 1);
+        assert!(Pin::new(&mut b).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut c).poll(&mut cx).is_pending());
+
+        assert_eq!(timer.fire_expired(at(5)), 1);
+        assert!(Pin::new(&mut c).poll(&mut cx).is_ready());
+        assert_eq!(timer.next_deadline(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn scoped_defaults() {
+        let mut a = Timer::new();
+        let mut b = Timer::new();
+        let at = Instant::now() + Duration::from_secs(3600);
+
+        let guard_a = a.handle().set_default();
+        let mut delay_a = Delay::new_at(at);
+        let guard_b = b.handle().set_default();
+        let mut delay_b = Delay::new_at(at);
+        drop(guard_b);
+        let mut delay_a2 = Delay::new_at(at);
+        drop(guard_a);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(a.fire_expired(at), 2);
+        assert!(Pin::new(&mut delay_a).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut delay_a2).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut delay_b).poll(&mut cx).is_pending());
+        assert_eq!(b.fire_expired(at), 1);
+        assert!(Pin::new(&mut delay_b).poll(&mut cx).is_ready());
+    }
+
+    #[wasm_bindgen_test]
+    #[should_panic(expected = "DefaultGuard dropped out of order")]
+    fn scoped_defaults_out_of_order() {
+        let a = Timer::new();
+        let b = Timer::new();
+        let guard_a = a.handle().set_default();
+        let _guard_b = b.handle().set_default();
+        drop(guard_a);
+    }
+
+    #[wasm_bindgen_test]
+    fn cancel_all() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let at = Instant::now() + Duration::from_secs(3600);
+        let mut a = Delay::new_handle(at, handle.clone());
+        poll_timer(&mut timer);
+        let mut b = Delay::new_handle(at, handle.clone());
+        assert_eq!(handle.cancel_all(), 1);
+        assert_eq!(timer.next_deadline(), None);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(matches!(
+            Pin::new(&mut a).poll(&mut cx),
+            Poll::Ready(Err(_))
+        ));
+        assert!(matches!(
+            Pin::new(&mut b).poll(&mut cx),
+            Poll::Ready(Err(_))
+        ));
+
+        let mut c = Delay::new_handle(at, handle.clone());
+        assert_eq!(timer.fire_expired(at), 1);
+        assert!(matches!(
+            Pin::new(&mut c).poll(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn lazy_cancellation() {
+        let mut timer = Timer::new();
+        timer.set_cancellation(Cancellation::Lazy);
+        let handle = timer.handle();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let heap_len = |timer: &Timer| timer.inner.timer_heap.lock().unwrap().len();
+        let mut delays: Vec<_> = (1..=4)
+            .map(|i| Delay::new_handle(at(i * 10), handle.clone()))
+            .collect();
+        poll_timer(&mut timer);
+
+        // The tombstone of the last delay stays in the heap, but never fires.
+        drop(delays.pop());
+        poll_timer(&mut timer);
+        assert_eq!(heap_len(&timer), 4);
+        assert_eq!(handle.active_count(), 3);
+
+        // Tombstones on top of the heap are discarded.
+        drop(delays.remove(0));
+        assert_eq!(timer.next_deadline(), Some(at(20)));
+        assert_eq!(heap_len(&timer), 3);
+
+        assert_eq!(timer.fire_expired(at(40)), 2);
+        assert_eq!(heap_len(&timer), 0);
+        assert_eq!(handle.stats().unwrap().total_fired, 2);
+
+        // Once more than half of the heap is cancelled, it gets compacted.
+        let mut delays: Vec<_> = (1..=4)
+            .map(|i| Delay::new_handle(at(i * 100), handle.clone()))
+            .collect();
+        poll_timer(&mut timer);
+        delays.truncate(1);
+        poll_timer(&mut timer);
+        assert_eq!(heap_len(&timer), 1);
+        assert_eq!(handle.active_count(), 1);
+        assert_eq!(timer.fire_expired(at(400)), 1);
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    #[test]
+    fn handle_shared_across_threads() {
+        use std::thread;
+
+        use futures::executor::block_on;
+
+        use crate::TimerHandle;
+
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<TimerHandle>();
+
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let handle = handle.clone();
+                thread::spawn(move || block_on(handle.delay(Duration::from_millis(10))))
+            })
+            .collect();
+        while !threads.iter().all(|t| t.is_finished()) {
+            timer.fire_expired(Instant::now());
+            thread::sleep(Duration::from_millis(1));
+        }
+        for t in threads {
+            assert!(t.join().unwrap().is_ok());
+        }
+        assert_eq!(handle.stats().unwrap().total_fired, 2);
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    #[test]
+    fn survives_poisoned_heap() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let at = Instant::now() + Duration::from_secs(3600);
+        let mut before = Delay::new_handle(at, handle.clone());
+        timer.next_deadline();
+
+        // Panic while the heap is locked, as a timer operation would on a
+        // bug.
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _heap = timer.inner.timer_heap.lock().unwrap();
+            panic!("timer operation panicked");
+        }));
+        assert!(panicked.is_err());
+        assert!(timer.inner.timer_heap.is_poisoned());
+
+        let mut after = Delay::new_handle(at, handle.clone());
+        assert_eq!(timer.next_deadline(), Some(at));
+        assert_eq!(timer.fire_expired(at), 2);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut before).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut after).poll(&mut cx).is_ready());
+        assert!(handle.stats().is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn stats() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let at = Instant::now() + Duration::from_secs(3600);
+        let stats = handle.stats().unwrap();
+        assert_eq!(stats.total_created, 0);
+        assert_eq!(stats.pending, 0);
+
+        let mut a = Delay::new_handle(at, handle.clone());
+        let b = Delay::new_handle(at + Duration::from_secs(1), handle.clone());
+        let c = Delay::new_handle(at + Duration::from_secs(2), handle.clone());
+        poll_timer(&mut timer);
+        drop(b);
+        assert_eq!(timer.fire_expired(at), 1);
+        assert!(Pin::new(&mut a)
+            .poll(&mut Context::from_waker(&noop_waker()))
+            .is_ready());
+        drop(a);
+
+        let stats = handle.stats().unwrap();
+        assert_eq!(stats.pending, 1);
+        assert_eq!(handle.active_count(), 1);
+        assert_eq!(stats.next_deadline, Some(at + Duration::from_secs(2)));
+        assert_eq!(stats.total_created, 3);
+        assert_eq!(stats.total_fired, 1);
+        assert_eq!(stats.total_cancelled, 1);
+        // The drop of `a` is yet to be processed.
+        assert_eq!(stats.queued_updates, 1);
+
+        handle.cancel_all();
+        drop(c);
+        let stats = handle.stats().unwrap();
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.total_cancelled, 2);
+
+        drop(timer);
+        assert_eq!(handle.stats(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn send_and_sync() {
+        use std::io;
+
+        use futures::future;
+
+        use crate::ext::Timeout;
+        use crate::TryFutureExt;
+
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        // On WASM as well, the state shared with the timer is behind atomics
+        // and mutexes, so everything can be sent to e.g. a web worker.
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let mut delay = Delay::new_handle(Instant::now(), timer.handle());
+        assert_send_sync(&delay.handle());
+        assert_send_sync(&delay);
+        assert_send_sync(&Interval::new_handle(
+            Instant::now(),
+            Duration::from_secs(1),
+            timer.handle(),
+        ));
+        let timeout: Timeout<future::Pending<io::Result<()>>> =
+            future::pending().timeout(Duration::from_secs(1));
+        assert_send_sync(&timeout);
+        assert_send_sync(&timer);
+        assert_send_sync(&timer.handle());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn poll_at_steps_through_deadlines() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut delays: Vec<_> = [30, 10, 20]
+            .into_iter()
+            .map(|ms| (ms, Delay::new_handle(at(ms), handle.clone())))
+            .collect();
+
+        let mut now = start;
+        let mut fired = Vec::new();
+        loop {
+            let idle = timer.poll_at(now, &mut cx).is_ready();
+            delays.retain_mut(|(ms, delay)| {
+                let ready = Pin::new(delay).poll(&mut cx).is_ready();
+                if ready {
+                    fired.push((*ms, now));
+                }
+                !ready
+            });
+            if idle {
+                break;
+            }
+            now = timer.next_deadline().unwrap();
+        }
+        assert_eq!(fired, [(10, at(10)), (20, at(20)), (30, at(30))]);
+        assert!(delays.is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn equal_deadlines_fire_in_creation_order() {
+        use std::sync::{Arc, Mutex};
+
+        use futures::task::{self, ArcWake};
+
+        /// Records its index when woken.
+        struct Recorder {
+            index: usize,
+            woken: Arc<Mutex<Vec<usize>>>,
+        }
+
+        impl ArcWake for Recorder {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.woken.lock().unwrap().push(arc_self.index);
+            }
+        }
+
+        let mut timer = Timer::new();
+        let woken = Arc::new(Mutex::new(Vec::new()));
+        let at = Instant::now() + Duration::from_secs(3600);
+        let waker = |index| {
+            task::waker(Arc::new(Recorder {
+                index,
+                woken: woken.clone(),
+            }))
+        };
+
+        let mut first = Delay::new_handle(at, timer.handle());
+        let mut interval = Interval::new_handle(at, Duration::from_secs(1), timer.handle());
+        let mut last = Delay::new_handle(at, timer.handle());
+        // Polled in another order than they were created in.
+        let wakers = [waker(0), waker(1), waker(2)];
+        assert!(Pin::new(&mut last)
+            .poll(&mut Context::from_waker(&wakers[2]))
+            .is_pending());
+        assert!(interval
+            .poll_tick(&mut Context::from_waker(&wakers[1]))
+            .is_pending());
+        assert!(Pin::new(&mut first)
+            .poll(&mut Context::from_waker(&wakers[0]))
+            .is_pending());
+
+        assert_eq!(timer.fire_expired(at), 3);
+        assert_eq!(*woken.lock().unwrap(), [0, 1, 2]);
+    }
+
+    #[wasm_bindgen_test]
+    fn shared_deadline_wakes_task_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+        use std::sync::Arc;
+
+        use futures::task::{self, ArcWake};
+
+        /// Counts how many times its task was woken.
+        struct Counter(AtomicUsize);
+
+        impl ArcWake for Counter {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.fetch_add(1, SeqCst);
+            }
+        }
+
+        let mut timer = Timer::new();
+        let at = Instant::now() + Duration::from_secs(3600);
+        let shared = Arc::new(Counter(AtomicUsize::new(0)));
+        let shared_waker = task::waker(shared.clone());
+        let own = Arc::new(Counter(AtomicUsize::new(0)));
+        let own_waker = task::waker(own.clone());
+
+        let mut delays: Vec<_> = (0..500)
+            .map(|_| Delay::new_handle(at, timer.handle()))
+            .collect();
+        for (i, delay) in delays.iter_mut().enumerate() {
+            let waker = if i == 250 { &own_waker } else { &shared_waker };
+            assert!(Pin::new(delay)
+                .poll(&mut Context::from_waker(waker))
+                .is_pending());
+        }
+        assert_eq!(timer.fire_expired(at), 500);
+        assert_eq!(shared.0.load(SeqCst), 1);
+        assert_eq!(own.0.load(SeqCst), 1);
+
+        // Every delay completes exactly once.
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        for delay in &mut delays {
+            assert!(matches!(
+                Pin::new(&mut *delay).poll(&mut cx),
+                Poll::Ready(Ok(()))
+            ));
+            assert!(Pin::new(delay).poll(&mut cx).is_pending());
+        }
+        assert_eq!(timer.fire_expired(at), 0);
+    }
+
+    #[wasm_bindgen_test]
+    fn bounded_fires_per_wakeup() {
+        use std::sync::{Arc, Mutex};
+
+        use futures::task::{self, ArcWake};
+
+        /// Records the index of its delay when woken.
+        struct Recorder {
+            index: usize,
+            woken: Arc<Mutex<Vec<usize>>>,
+        }
+
+        impl ArcWake for Recorder {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.woken.lock().unwrap().push(arc_self.index);
+            }
+        }
+
+        let mut timer = Timer::new();
+        timer.set_max_fires_per_wakeup(50);
+        let woken = Arc::new(Mutex::new(Vec::new()));
+        let now = Instant::now() + Duration::from_secs(3600);
+        let at = |i: usize| now + Duration::from_secs(i as u64 % 2);
+
+        // Delays sharing a deadline are completed in the order they were
+        // scheduled in, which differs from their creation order for the
+        // ones which are reset.
+        let mut delays: Vec<_> = (0..1000)
+            .map(|i| Delay::new_handle(at(i), timer.handle()))
+            .collect();
+        timer.next_deadline();
+        delays[4].reset_at(at(4));
+        for (index, delay) in delays.iter_mut().enumerate() {
+            let waker = task::waker(Arc::new(Recorder {
+                index,
+                woken: woken.clone(),
+            }));
+            assert!(Pin::new(delay)
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending());
+        }
+
+        let mut wakeups = 0;
+        while timer.fire_expired(at(1)) > 0 {
+            wakeups += 1;
+        }
+        assert_eq!(wakeups, 1000 / 50);
+
+        let mut expected: Vec<_> = (0..1000).filter(|i| i % 2 == 0 && *i != 4).collect();
+        expected.push(4);
+        expected.extend((0..1000).filter(|i| i % 2 == 1));
+        assert_eq!(*woken.lock().unwrap(), expected);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[wasm_bindgen_test]
+    fn tracing_events() {
+        use std::sync::{Arc, Mutex};
+
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Message and delay identifier of every event emitted so far.
+        type Events = Arc<Mutex<Vec<(String, String)>>>;
+
+        struct Recorder(Events);
+
+        #[derive(Default)]
+        struct Fields {
+            message: String,
+            delay_id: String,
+        }
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                match field.name() {
+                    "message" => self.message = format!("{:?}", value),
+                    "delay_id" => self.delay_id = format!("{:?}", value),
+                    _ => {}
+                }
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((fields.message, fields.delay_id));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let events = Events::default();
+        let recorder = Recorder(events.clone());
+        tracing::subscriber::with_default(recorder, || {
+            let mut timer = Timer::new();
+            let at = Instant::now() + Duration::from_secs(3600);
+            let mut a = Delay::new_handle(at, timer.handle());
+            let b = Delay::new_handle(at, timer.handle());
+            timer.next_deadline();
+            a.reset_at(at + Duration::from_secs(1));
+            drop(b);
+            assert_eq!(timer.fire_expired(at + Duration::from_secs(1)), 1);
+        });
+
+        let events = events.lock().unwrap();
+        let (a, b) = (events[0].1.clone(), events[1].1.clone());
+        assert_ne!(a, b);
+        let expected = [
+            ("delay armed", &a),
+            ("delay armed", &b),
+            ("delay armed", &a),
+            ("delay cancelled", &b),
+            ("delay fired", &a),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(message, id)| (message.to_string(), id.clone()))
+            .collect();
+        assert_eq!(*events, expected);
+    }
+}
@@ -0,0 +1,102 @@
+//! Cancellation of delays through the `AbortSignal` of the browser.
+//!
+//! This lets JavaScript code cancel a `Delay` or a `Timeout` with the same
+//! `AbortController` as the `fetch` requests they're racing against.
+
+use std::cell::RefCell;
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::AbortSignal;
+
+use super::DelayHandle;
+
+thread_local! {
+    /// Listeners currently registered.
+    static LISTENERS: RefCell<Vec<Listener>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An `abort` listener registered for a delay.
+///
+/// These are kept here rather than in the `Delay`, which must stay `Send`.
+struct Listener {
+    /// Address of the state shared by the delay and its handles.
+    key: usize,
+    signal: AbortSignal,
+    callback: Closure<dyn FnMut()>,
+}
+
+/// Cancels the delay of `handle` when `signal` aborts, or right away if it
+/// already has.
+pub(crate) fn listen(key: usize, signal: &AbortSignal, handle: DelayHandle) {
+    if signal.aborted() {
+        handle.cancel();
+        return;
+    }
+    let callback = Closure::wrap(Box::new(move || handle.cancel()) as Box<dyn FnMut()>);
+    signal
+        .add_event_listener_with_callback("abort", callback.as_ref().unchecked_ref())
+        .expect("failed to add an abort listener");
+    LISTENERS.with(|listeners| {
+        listeners.borrow_mut().push(Listener {
+            key,
+            signal: signal.clone(),
+            callback,
+        })
+    });
+}
+
+/// Removes the listeners registered for the given delay, if any.
+pub(crate) fn unlisten(key: usize) {
+    let removed = LISTENERS.with(|listeners| {
+        let mut listeners = listeners.borrow_mut();
+        let (removed, kept) = listeners.drain(..).partition(|l| l.key == key);
+        *listeners = kept;
+        removed
+    });
+    for listener in removed {
+        let Listener {
+            signal, callback, ..
+        } = listener;
+        let _ =
+            signal.remove_event_listener_with_callback("abort", callback.as_ref().unchecked_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use web_sys::AbortController;
+
+    use super::LISTENERS;
+    use crate::{Delay, DelayCancelled, Instant, TryFutureExt};
+
+    fn is_cancelled(err: &std::io::Error) -> bool {
+        err.get_ref().is_some_and(|err| err.is::<DelayCancelled>())
+    }
+
+    #[wasm_bindgen_test]
+    async fn abort_resolves_timeout() {
+        let controller = AbortController::new().unwrap();
+        let start = Instant::now();
+        let timeout = future::pending::<std::io::Result<()>>()
+            .timeout(Duration::from_secs(10))
+            .with_abort_signal(&controller.signal());
+        crate::spawn_after(Duration::from_millis(10), move || controller.abort()).forget();
+
+        let err = timeout.await.unwrap_err();
+        assert!(is_cancelled(&err));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(LISTENERS.with(|l| l.borrow().len()), 0);
+    }
+
+    #[wasm_bindgen_test]
+    async fn already_aborted() {
+        let controller = AbortController::new().unwrap();
+        controller.abort();
+        let delay = Delay::new(Duration::from_secs(10)).with_abort_signal(&controller.signal());
+        assert!(is_cancelled(&delay.await.unwrap_err()));
+    }
+}
@@ -1,24 +1,53 @@
 //! An atomically managed intrusive linked list of `Arc` nodes
 
 use std::marker;
-use std::ops::Deref;
-use std::sync::atomic::Ordering::SeqCst;
+use std::ops::{Deref, DerefMut};
+
+#[cfg(wasm_timer_loom)]
+use loom::sync::atomic::Ordering::{Relaxed, SeqCst};
+#[cfg(wasm_timer_loom)]
+use loom::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(wasm_timer_loom)]
+use loom::sync::Arc;
+#[cfg(not(wasm_timer_loom))]
+use std::sync::atomic::Ordering::{Relaxed, SeqCst};
+#[cfg(not(wasm_timer_loom))]
 use std::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(not(wasm_timer_loom))]
 use std::sync::Arc;
 
+/// A list of nodes, popped in the reverse order of their pushes.
+///
+/// Once sealed, pushes fail. A push which succeeds is always seen by the
+/// owner of the list, be it through `take` or `take_and_seal`.
 pub struct ArcList<T> {
     list: AtomicUsize,
+    len: AtomicUsize,
     _marker: marker::PhantomData<T>,
 }
 
 impl<T> ArcList<T> {
     pub fn new() -> ArcList<T> {
+        ArcList::from_head(0, 0)
+    }
+
+    fn from_head(head: usize, len: usize) -> ArcList<T> {
         ArcList {
-            list: AtomicUsize::new(0),
+            list: AtomicUsize::new(head),
+            len: AtomicUsize::new(len),
             _marker: marker::PhantomData,
         }
     }
 
+    /// Returns the number of nodes in this list.
+    ///
+    /// This is only approximate while other threads push nodes or take the
+    /// list, and meant for heuristics such as bounding the work done per
+    /// wake-up.
+    pub fn len(&self) -> usize {
+        self.len.load(Relaxed)
+    }
+
     /// Pushes the `data` provided onto this list if it's not already enqueued
     /// in this list.
     ///
@@ -33,18 +62,24 @@ impl<T> ArcList<T> {
         let mut head = self.list.load(SeqCst);
         let node = Arc::into_raw(data.clone()) as usize;
         loop {
-            // If we've been sealed off, abort and return an error
+            // If we've been sealed off, abort and return an error. The node
+            // isn't enqueued anywhere, so later pushes must fail as well
+            // rather than be taken for no-ops.
             if head == 1 {
                 unsafe {
                     drop(Arc::from_raw(node as *mut Node<T>));
                 }
+                data.enqueued.store(false, SeqCst);
                 return Err(());
             }
 
             // Otherwise attempt to push this node
             data.next.store(head, SeqCst);
             match self.list.compare_exchange(head, node, SeqCst, SeqCst) {
-                Ok(_) => break Ok(()),
+                Ok(_) => {
+                    self.len.fetch_add(1, Relaxed);
+                    break Ok(());
+                }
                 Err(new_head) => head = new_head,
             }
         }
@@ -63,35 +98,55 @@ impl<T> ArcList<T> {
                 Err(l) => list = l,
             }
         }
-        ArcList {
-            list: AtomicUsize::new(list),
-            _marker: marker::PhantomData,
-        }
+        ArcList::from_head(list, self.len.swap(0, Relaxed))
     }
 
     /// Atomically empties this list and prevents further successful calls to
     /// `push`.
     pub fn take_and_seal(&self) -> ArcList<T> {
-        ArcList {
-            list: AtomicUsize::new(self.list.swap(1, SeqCst)),
-            _marker: marker::PhantomData,
-        }
+        let list = self.list.swap(1, SeqCst);
+        ArcList::from_head(list, self.len.swap(0, Relaxed))
     }
 
     /// Removes the head of the list of nodes, returning `None` if this is an
     /// empty list.
     pub fn pop(&mut self) -> Option<Arc<Node<T>>> {
-        let head = *self.list.get_mut();
+        let head = self.list.load(SeqCst);
         if head == 0 || head == 1 {
             return None;
         }
         let head = unsafe { Arc::from_raw(head as *const Node<T>) };
-        *self.list.get_mut() = head.next.load(SeqCst);
+        self.list.store(head.next.load(SeqCst), SeqCst);
+        // The counter may lag behind pushes which raced with `take`.
+        let _ = self
+            .len
+            .fetch_update(Relaxed, Relaxed, |len| len.checked_sub(1));
         // At this point, the node is out of the list, so store `false` so we
         // can enqueue it again and see further changes.
         assert!(head.enqueued.swap(false, SeqCst));
         Some(head)
     }
+
+    /// Returns an iterator popping the nodes of this list one at a time.
+    ///
+    /// Nodes which aren't iterated over stay in the list, so that a driver
+    /// can process them over several wake-ups.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain { list: self }
+    }
+}
+
+/// Iterator returned by `ArcList::drain`.
+pub struct Drain<'a, T> {
+    list: &'a mut ArcList<T>,
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = Arc<Node<T>>;
+
+    fn next(&mut self) -> Option<Arc<Node<T>>> {
+        self.list.pop()
+    }
 }
 
 impl<T> Drop for ArcList<T> {
@@ -126,7 +181,13 @@ impl<T> Deref for Node<T> {
     }
 }
 
-#[cfg(test)]
+impl<T> DerefMut for Node<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+#[cfg(all(test, not(wasm_timer_loom)))]
 mod tests {
     use wasm_bindgen_test::wasm_bindgen_test;
 
@@ -154,4 +215,107 @@ mod tests {
         assert!(a.take().pop().is_none());
         assert!(a.take_and_seal().pop().is_none());
     }
+
+    #[wasm_bindgen_test]
+    fn drain_in_reverse_order() {
+        let a = ArcList::new();
+        let nodes: Vec<_> = (0..3).map(|i| Arc::new(Node::new(i))).collect();
+        for n in &nodes {
+            assert!(a.push(n).is_ok());
+        }
+        // Pushing an enqueued node again is a no-op.
+        assert!(a.push(&nodes[0]).is_ok());
+        assert_eq!(a.len(), 3);
+
+        let mut l = a.take();
+        assert_eq!(a.len(), 0);
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.drain().next().map(|n| **n), Some(2));
+        assert_eq!(l.len(), 2);
+        assert_eq!(l.drain().map(|n| **n).collect::<Vec<_>>(), [1, 0]);
+    }
+
+    #[wasm_bindgen_test]
+    fn push_after_seal_keeps_failing() {
+        let a = ArcList::new();
+        let n = Arc::new(Node::new(1));
+        drop(a.take_and_seal());
+        assert!(a.push(&n).is_err());
+        assert!(a.push(&n).is_err());
+    }
+}
+
+#[cfg(all(test, wasm_timer_loom))]
+mod loom_tests {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::*;
+
+    #[test]
+    fn push_and_take() {
+        loom::model(|| {
+            let a = Arc::new(ArcList::new());
+            let pusher = {
+                let a = a.clone();
+                thread::spawn(move || {
+                    for i in 0..2 {
+                        assert!(a.push(&Arc::new(Node::new(i))).is_ok());
+                    }
+                })
+            };
+            let mut seen: Vec<_> = a.take().drain().map(|n| **n).collect();
+            pusher.join().unwrap();
+            seen.extend(a.take().drain().map(|n| **n));
+            seen.sort();
+            assert_eq!(seen, [0, 1]);
+        });
+    }
+
+    #[test]
+    fn push_and_seal() {
+        loom::model(|| {
+            let a = Arc::new(ArcList::new());
+            let n = Arc::new(Node::new(1));
+            let pusher = {
+                let a = a.clone();
+                let n = n.clone();
+                thread::spawn(move || a.push(&n).is_ok())
+            };
+            let sealed: Vec<_> = a.take_and_seal().drain().map(|n| **n).collect();
+            let pushed = pusher.join().unwrap();
+            // A successful push is seen by the sealer, a failed one isn't.
+            assert_eq!(sealed == [1], pushed);
+            assert!(a.push(&n).is_err());
+        });
+    }
+
+    #[test]
+    fn repush_and_seal() {
+        loom::model(|| {
+            let a = Arc::new(ArcList::new());
+            let n = Arc::new(Node::new(1));
+            assert!(a.push(&n).is_ok());
+            let pusher = {
+                let a = a.clone();
+                let n = n.clone();
+                thread::spawn(move || {
+                    let mut taken = a.take();
+                    let popped = taken.drain().count();
+                    (popped, a.push(&n).is_ok())
+                })
+            };
+            let sealed = a.take_and_seal().drain().count();
+            let (popped, pushed) = pusher.join().unwrap();
+            if popped == 1 {
+                // The node was pushed anew, which only succeeds if the sealer
+                // sees it.
+                assert_eq!(sealed == 1, pushed);
+            } else {
+                // The sealer took the node, and pushing it again is either a
+                // no-op or fails.
+                assert_eq!(sealed, 1);
+            }
+        });
+    }
 }
@@ -0,0 +1,88 @@
+//! Combinators waiting on several futures under a deadline.
+
+use std::task::Poll;
+
+use futures::prelude::*;
+
+use crate::timer::error::Elapsed;
+use crate::{Delay, Instant};
+
+/// Waits for the first of `futs` to complete, but no longer than until
+/// `deadline`.
+///
+/// On success, returns the output of the future which completed, its index in
+/// `futs`, and the other futures, in their original order without it. On
+/// timeout, returns all of `futs` along with `Elapsed`, so that the caller can
+/// keep waiting on them or drop them. If `futs` is empty, this times out at
+/// `deadline`.
+///
+/// Contrary to wrapping each future in a `Timeout`, a single `Delay` is used
+/// for all of them.
+pub async fn first_of_with_deadline<F>(
+    mut futs: Vec<F>,
+    deadline: Instant,
+) -> Result<(F::Output, usize, Vec<F>), (Elapsed, Vec<F>)>
+where
+    F: Future + Unpin,
+{
+    let mut delay = Delay::new_at(deadline);
+    let first = future::poll_fn(|cx| {
+        for (index, fut) in futs.iter_mut().enumerate() {
+            if let Poll::Ready(output) = fut.poll_unpin(cx) {
+                return Poll::Ready(Some((output, index)));
+            }
+        }
+        // The timer being gone is taken for the deadline having elapsed, as
+        // with `timeout_reclaim`.
+        delay.poll_unpin(cx).map(|_| None)
+    })
+    .await;
+    match first {
+        Some((output, index)) => {
+            futs.remove(index);
+            Ok((output, index, futs))
+        }
+        None => Err((Elapsed(()), futs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::channel::oneshot;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::first_of_with_deadline;
+    use crate::Instant;
+
+    #[wasm_bindgen_test]
+    async fn first_future_wins() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| oneshot::channel()).unzip();
+        let mut senders = senders.into_iter();
+        let _first = senders.next().unwrap();
+        senders.next().unwrap().send(5).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let (output, index, rest) = first_of_with_deadline(receivers, deadline).await.unwrap();
+        assert_eq!(output, Ok(5));
+        assert_eq!(index, 1);
+        assert_eq!(rest.len(), 2);
+    }
+
+    #[wasm_bindgen_test]
+    async fn deadline_elapses() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| oneshot::channel()).unzip();
+        let deadline = Instant::now() + Duration::from_millis(10);
+        let (_, mut futs) = first_of_with_deadline(receivers, deadline)
+            .await
+            .unwrap_err();
+        assert_eq!(futs.len(), 3);
+
+        // The futures which are given back can still complete.
+        for (i, sender) in senders.into_iter().enumerate() {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(futs.pop().unwrap().await, Ok(2));
+    }
+}
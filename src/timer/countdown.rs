@@ -0,0 +1,149 @@
+//! A stream counting down to a deadline.
+
+use std::cmp;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::prelude::*;
+use futures::stream::FusedStream;
+
+use crate::timer::{self, delay, saturating_add};
+use crate::{Delay, Instant};
+
+/// A stream which yields the time remaining until a deadline, every
+/// `resolution`, e.g. to display a countdown.
+///
+/// The last item is `Duration::ZERO`, yielded at the deadline, after which
+/// the stream ends.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use futures::prelude::*;
+/// use zduny_wasm_timer::CountdownTimer;
+///
+/// # async fn example() {
+/// let mut countdown = CountdownTimer::new(Duration::from_secs(5), Duration::from_secs(1));
+/// while let Some(remaining) = countdown.next().await {
+///     println!("{}...", remaining.as_secs());
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CountdownTimer {
+    delay: Delay,
+    deadline: Instant,
+    resolution: Duration,
+    done: bool,
+}
+
+impl CountdownTimer {
+    /// Creates a countdown of `duration`, ticking every `resolution`.
+    ///
+    /// The returned object will be bound to the default timer for this thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `resolution` is zero.
+    pub fn new(duration: Duration, resolution: Duration) -> CountdownTimer {
+        assert!(resolution > Duration::ZERO, "resolution must be non-zero");
+        let now = timer::now();
+        let deadline = saturating_add(now, duration);
+        let first = cmp::min(saturating_add(now, resolution), deadline);
+        CountdownTimer {
+            delay: Delay::new_at(first),
+            deadline,
+            resolution,
+            done: false,
+        }
+    }
+
+    /// Returns the instant the countdown ends at.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl Stream for CountdownTimer {
+    type Item = Duration;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Duration>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        match Pin::new(&mut this.delay).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(_)) => {
+                // The timer is gone, so the countdown can't go on.
+                this.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Ready(Ok(())) => {}
+        }
+        let at = delay::fires_at(&this.delay);
+        let now = this.delay.fired_at().unwrap_or_else(timer::now);
+        if at >= this.deadline || now >= this.deadline {
+            this.done = true;
+            return Poll::Ready(Some(Duration::ZERO));
+        }
+        let next = cmp::min(saturating_add(at, this.resolution), this.deadline);
+        this.delay.reset_at(next);
+        Poll::Ready(Some(this.deadline - now))
+    }
+}
+
+impl FusedStream for CountdownTimer {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::stream::FusedStream;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::CountdownTimer;
+    use crate::Timer;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[wasm_bindgen_test]
+    fn counts_down() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut countdown = CountdownTimer::new(secs(5), secs(2));
+        let start = countdown.deadline() - secs(5);
+        assert_eq!(countdown.poll_next_unpin(&mut cx), Poll::Pending);
+        timer.fire_expired(start + secs(2));
+        assert_eq!(
+            countdown.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(secs(3)))
+        );
+        assert_eq!(countdown.poll_next_unpin(&mut cx), Poll::Pending);
+        // A late tick yields the actual remaining time.
+        timer.fire_expired(start + secs(4) + Duration::from_millis(500));
+        assert_eq!(
+            countdown.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Duration::from_millis(500)))
+        );
+        timer.fire_expired(start + secs(5));
+        assert_eq!(
+            countdown.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Duration::ZERO))
+        );
+        assert_eq!(countdown.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert!(countdown.is_terminated());
+    }
+}
@@ -2,22 +2,64 @@
 //!
 //! This module contains the `Delay` type which is a future that will resolve
 //! at a particular point in the future.
+//!
+//! With the `tokio` feature enabled on non-WASM targets, delays which aren't
+//! bound to an explicit `TimerHandle` are backed by `tokio::time::Sleep`
+//! instead of the global timer, and therefore require a running tokio
+//! runtime.
 
+use std::cmp;
 use std::fmt;
 use std::future::Future;
 use std::io;
 use std::pin::Pin;
-use std::sync::atomic::AtomicUsize;
+#[cfg(any(feature = "tracing", feature = "log"))]
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering::SeqCst;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 
+use futures::future::{FusedFuture, FutureExt};
 use futures::task::AtomicWaker;
 
+use crate::cancel::CancellationToken;
 use crate::timer::arc_list::Node;
-use crate::timer::{ScheduledTimer, TimerHandle};
-use crate::Instant;
+use crate::timer::error::{DelayCancelled, TimerDropped};
+use crate::timer::{self, ScheduledTimer, SchedulerHint, TimerHandle};
+use crate::{Instant, SystemTime};
+
+/// Longest hop of a delay created with `Delay::new_at_system`, i.e. how late
+/// it may notice that the wall clock jumped forward.
+const WALL_CLOCK_HOP: Duration = Duration::from_secs(60);
+
+/// Deadline of a delay on the wall clock.
+#[derive(Debug)]
+struct WallDeadline {
+    when: SystemTime,
+    /// Source of the wall clock, which tests replace.
+    now: fn() -> SystemTime,
+}
+
+impl WallDeadline {
+    /// Returns how long the wall clock has to go until the deadline.
+    fn remaining(&self) -> Duration {
+        self.when
+            .duration_since((self.now)())
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Source of the identifiers of delays in events.
+#[cfg(any(feature = "tracing", feature = "log"))]
+static NEXT_DELAY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Alias of `Delay`, for code written against the naming of `tokio::time`.
+///
+/// `sleep` predates this alias and resolves to `()`, so it doesn't return a
+/// `Sleep`; use `Sleep::new` for a `Sleep` which waits for a duration.
+pub type Sleep = Delay;
 
 /// A future representing the notification that an elapsed duration has
 /// occurred.
@@ -27,9 +69,29 @@ use crate::Instant;
 /// intended for high resolution timers, but rather they will likely fire some
 /// granularity after the exact instant that they're otherwise indicated to
 /// fire at.
+///
+/// Once a `Delay` has resolved, polling it again returns `Poll::Pending` until
+/// it is reset, and `FusedFuture::is_terminated` returns `true`. This makes it
+/// safe to use in `select!` without `.fuse()`.
 pub struct Delay {
     state: Option<Arc<Node<ScheduledTimer>>>,
     when: Instant,
+    fired_at: Option<Instant>,
+    terminated: bool,
+    /// State shared with the `DelayHandle`s of this delay, if any.
+    control: Option<Arc<Control>>,
+    /// Wall clock deadline of delays created with `new_at_system`.
+    wall: Option<WallDeadline>,
+    /// Whether this delay returns `Pending` at least once before resolving,
+    /// see `new_cooperative`.
+    cooperative: bool,
+    /// Whether `Pending` was returned since the delay last resolved.
+    yielded: bool,
+    #[cfg(all(
+        feature = "tokio",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    ))]
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
 }
 
 impl Delay {
@@ -37,9 +99,63 @@ impl Delay {
     ///
     /// The returned object will be bound to the default timer for this thread.
     /// The default timer will be spun up in a helper thread on first use.
+    ///
+    /// Any duration is allowed: if the deadline can't be represented as an
+    /// `Instant`, e.g. for `Duration::MAX`, it is clamped to the latest one
+    /// that can, so the delay never fires in practice. See `new_or_panic` to
+    /// catch such durations instead.
     #[inline]
     pub fn new(dur: Duration) -> Delay {
-        Delay::new_at(Instant::now() + dur)
+        Delay::new_at(timer::saturating_add(timer::now(), dur))
+    }
+
+    /// Same as `Delay::new`, but panics if the deadline can't be represented
+    /// as an `Instant`, rather than clamping it.
+    ///
+    /// How far in the future an `Instant` can be depends on the platform, but
+    /// it's at least centuries away for all of them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dur` from now overflows an `Instant`.
+    pub fn new_or_panic(dur: Duration) -> Delay {
+        match timer::now().checked_add(dur) {
+            Some(at) => Delay::new_at(at),
+            None => panic!("the deadline of a delay of {:?} overflows an Instant", dur),
+        }
+    }
+
+    /// Creates a new future which will fire at the `std::time::Instant`
+    /// specified by `at`, e.g. a deadline obtained from another library.
+    ///
+    /// Outside of WASM, `Instant` is a re-export of `std::time::Instant`, so
+    /// this is the same as `new_at`, and no conversion is needed between the
+    /// two.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn new_at_std(at: std::time::Instant) -> Delay {
+        Delay::new_at(at)
+    }
+
+    /// Creates a new future which will fire once the wall clock reaches
+    /// `when`, e.g. for calendar-style scheduling.
+    ///
+    /// Contrary to `new_at`, this honors adjustments of the system clock: the
+    /// delay wakes up at least every minute to recompute the remaining time
+    /// from `SystemTime::now`, so it fires at most a minute late if the clock
+    /// jumps forward, and later if it steps backward. If `when` is in the
+    /// past, the delay fires immediately.
+    ///
+    /// `deadline` returns the instant of the next wake-up of such a delay, and
+    /// resetting it turns it into a regular delay.
+    pub fn new_at_system(when: SystemTime) -> Delay {
+        Delay::new_at_system_with_clock(when, SystemTime::now)
+    }
+
+    fn new_at_system_with_clock(when: SystemTime, now: fn() -> SystemTime) -> Delay {
+        let wall = WallDeadline { when, now };
+        let mut delay = Delay::new(cmp::min(wall.remaining(), WALL_CLOCK_HOP));
+        delay.wall = Some(wall);
+        delay
     }
 
     /// Creates a new future which will fire at the time specified by `at`.
@@ -47,10 +163,86 @@ impl Delay {
     /// The returned object will be bound to the default timer for this thread.
     /// The default timer will be spun up in a helper thread on first use.
     #[inline]
+    #[cfg(not(all(
+        feature = "tokio",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    )))]
     pub fn new_at(at: Instant) -> Delay {
         Delay::new_handle(at, Default::default())
     }
 
+    /// Creates a new future which will fire at the time specified by `at`.
+    ///
+    /// Unless a default handle was installed with `TimerHandle::set_default`,
+    /// the returned object is backed by `tokio::time::Sleep`, and as such must
+    /// be created from within a tokio runtime.
+    #[inline]
+    #[cfg(all(
+        feature = "tokio",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    ))]
+    pub fn new_at(at: Instant) -> Delay {
+        if let Some(handle) = TimerHandle::scoped_default() {
+            return Delay::new_handle(at, handle);
+        }
+        Delay {
+            state: None,
+            when: at,
+            fired_at: None,
+            terminated: false,
+            control: None,
+            wall: None,
+            cooperative: false,
+            yielded: false,
+            sleep: Some(Box::pin(tokio::time::sleep_until(at.into()))),
+        }
+    }
+
+    /// Same as `Delay::new`, but the delay yields to the executor before
+    /// resolving, even if its deadline has already passed when it's first
+    /// polled.
+    ///
+    /// Such a delay returns `Poll::Pending`, and wakes its task right away,
+    /// rather than resolving on the first poll after it was created or reset.
+    /// This avoids starving the other tasks of a single-threaded executor,
+    /// e.g. on WASM, in loops awaiting delays which are often already
+    /// elapsed.
+    pub fn new_cooperative(dur: Duration) -> Delay {
+        let mut delay = Delay::new(dur);
+        delay.cooperative = true;
+        delay
+    }
+
+    /// Same as `Delay::new`, but fails if the timer backend isn't available,
+    /// rather than returning a delay which resolves to an error.
+    ///
+    /// This is the case if the global timer couldn't be started, or if the
+    /// timer of the handle installed with `TimerHandle::set_default` is gone.
+    /// With the `tokio` feature, this is also the case outside of a tokio
+    /// runtime, unless a default handle is installed.
+    pub fn try_new(dur: Duration) -> io::Result<Delay> {
+        Delay::try_new_at(timer::saturating_add(timer::now(), dur))
+    }
+
+    /// Same as `Delay::new_at`, but fails if the timer backend isn't
+    /// available. See `Delay::try_new`.
+    pub fn try_new_at(at: Instant) -> io::Result<Delay> {
+        #[cfg(all(
+            feature = "tokio",
+            not(all(target_arch = "wasm32", target_os = "unknown"))
+        ))]
+        if TimerHandle::scoped_default().is_none() {
+            // `tokio::time::sleep_until` panics outside of a runtime.
+            tokio::runtime::Handle::try_current().map_err(io::Error::other)?;
+            return Ok(Delay::new_at(at));
+        }
+        let delay = Delay::new_handle(at, TimerHandle::try_default()?);
+        if delay.state.is_none() {
+            return Err(TimerDropped(()).into());
+        }
+        Ok(delay)
+    }
+
     /// Creates a new future which will fire at the time specified by `at`.
     ///
     /// The returned instance of `Delay` will be bound to the timer specified by
@@ -62,16 +254,32 @@ impl Delay {
                 return Delay {
                     state: None,
                     when: at,
+                    fired_at: None,
+                    terminated: false,
+                    control: None,
+                    wall: None,
+                    cooperative: false,
+                    yielded: false,
+                    #[cfg(all(
+                        feature = "tokio",
+                        not(all(target_arch = "wasm32", target_os = "unknown"))
+                    ))]
+                    sleep: None,
                 }
             }
         };
-        let state = Arc::new(Node::new(ScheduledTimer {
+        let state = inner.node(ScheduledTimer {
             at: Mutex::new(Some(at)),
             state: AtomicUsize::new(0),
             waker: AtomicWaker::new(),
             inner: handle.inner,
+            fired_at: Mutex::new(None),
             slot: Mutex::new(None),
-        }));
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+            hint: Mutex::new(SchedulerHint::default()),
+            #[cfg(any(feature = "tracing", feature = "log"))]
+            id: NEXT_DELAY_ID.fetch_add(1, SeqCst),
+        });
 
         // If we fail to actually push our node then we've become an inert
         // timer, meaning that we'll want to immediately return an error from
@@ -80,13 +288,36 @@ impl Delay {
             return Delay {
                 state: None,
                 when: at,
+                fired_at: None,
+                terminated: false,
+                control: None,
+                wall: None,
+                cooperative: false,
+                yielded: false,
+                #[cfg(all(
+                    feature = "tokio",
+                    not(all(target_arch = "wasm32", target_os = "unknown"))
+                ))]
+                sleep: None,
             };
         }
 
+        inner.total_created.fetch_add(1, SeqCst);
         inner.waker.wake();
         Delay {
             state: Some(state),
             when: at,
+            fired_at: None,
+            terminated: false,
+            control: None,
+            wall: None,
+            cooperative: false,
+            yielded: false,
+            #[cfg(all(
+                feature = "tokio",
+                not(all(target_arch = "wasm32", target_os = "unknown"))
+            ))]
+            sleep: None,
         }
     }
 
@@ -96,7 +327,7 @@ impl Delay {
     /// This is equivalent to calling `reset_at` with `Instant::now() + dur`
     #[inline]
     pub fn reset(&mut self, dur: Duration) {
-        self.reset_at(Instant::now() + dur)
+        self.reset_at(timer::saturating_add(timer::now(), dur))
     }
 
     /// Resets this timeout to an new timeout which will fire at the time
@@ -114,12 +345,141 @@ impl Delay {
     /// has been called to ensure tha ta task is blocked on this future.
     #[inline]
     pub fn reset_at(&mut self, at: Instant) {
+        self.fired_at = None;
+        self.terminated = false;
+        self.wall = None;
+        self.yielded = false;
+        if let Some(ref control) = self.control {
+            control.cancelled.store(false, SeqCst);
+            control.done.store(false, SeqCst);
+        }
+        self.rearm(at);
+    }
+
+    /// Schedules this delay to fire at `at`, without touching the rest of its
+    /// state.
+    fn rearm(&mut self, at: Instant) {
         self.when = at;
+        #[cfg(all(
+            feature = "tokio",
+            not(all(target_arch = "wasm32", target_os = "unknown"))
+        ))]
+        if let Some(ref mut sleep) = self.sleep {
+            sleep.as_mut().reset(at.into());
+            return;
+        }
         if self._reset(at).is_err() {
             self.state = None
         }
     }
 
+    /// Returns a handle which can cancel or reset this delay from other tasks.
+    ///
+    /// All the handles of a delay control it alike, and they can be cloned.
+    pub fn handle(&mut self) -> DelayHandle {
+        let terminated = self.terminated;
+        let control = self.control.get_or_insert_with(|| {
+            Arc::new(Control {
+                cancelled: AtomicBool::new(false),
+                reset: Mutex::new(None),
+                done: AtomicBool::new(terminated),
+                waker: AtomicWaker::new(),
+            })
+        });
+        DelayHandle {
+            control: control.clone(),
+        }
+    }
+
+    /// Returns the instant this delay fires at, as of its creation or last
+    /// reset.
+    ///
+    /// A reset requested through a `DelayHandle` is only reflected once the
+    /// delay is polled.
+    pub fn deadline(&self) -> Instant {
+        self.when
+    }
+
+    /// Returns when the timer fired this delay, or `None` if it hasn't
+    /// resolved successfully since it was created or last reset.
+    ///
+    /// This is the time at which the timer processed the expired delays, so
+    /// all the delays fired during the same wake-up share the same value.
+    pub fn fired_at(&self) -> Option<Instant> {
+        self.fired_at
+    }
+
+    /// Returns how long after its deadline this delay was fired, or `None` if
+    /// it hasn't fired yet.
+    ///
+    /// This is zero if the delay was fired on time, or early, which may happen
+    /// on WASM since deadlines are rounded to the millisecond there.
+    pub fn lateness(&self) -> Option<Duration> {
+        self.fired_at
+            .map(|fired_at| timer::lateness(self.when, fired_at))
+    }
+
+    /// Sets how the WASM timer should schedule its wake-up for this delay.
+    ///
+    /// The hint is taken into account the next time the timer schedules a
+    /// wake-up for this delay, and is ignored on other targets. See
+    /// `SchedulerHint` for details.
+    pub fn set_scheduler_hint(&mut self, hint: SchedulerHint) {
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        if let Some(ref state) = self.state {
+            *timer::lock(&state.hint) = hint;
+        }
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        let _ = hint;
+    }
+
+    /// Same as `set_scheduler_hint`, but consumes and returns the delay, so
+    /// that it can be configured and awaited in a single expression.
+    ///
+    /// `Delay` is a `Future`, so it is its own `IntoFuture` and can be awaited
+    /// right after such calls.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use zduny_wasm_timer::{Delay, SchedulerHint};
+    ///
+    /// # async fn example() -> std::io::Result<()> {
+    /// Delay::new(Duration::from_millis(16))
+    ///     .with_scheduler_hint(SchedulerHint::AnimationFrame)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_scheduler_hint(mut self, hint: SchedulerHint) -> Delay {
+        self.set_scheduler_hint(hint);
+        self
+    }
+
+    /// Cancels this delay when `token` is cancelled, as with
+    /// `DelayHandle::cancel`.
+    ///
+    /// The delay then resolves with a `DelayCancelled` error, right away if
+    /// the token is already cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Delay {
+        token.attach(self.handle());
+        self
+    }
+
+    /// Cancels this delay when `signal` aborts, as with `DelayHandle::cancel`.
+    ///
+    /// The delay then resolves with a `DelayCancelled` error, right away if
+    /// the signal has already aborted. The listener is removed when the delay
+    /// is dropped.
+    ///
+    /// This is only available on WASM.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn with_abort_signal(mut self, signal: &web_sys::AbortSignal) -> Delay {
+        let handle = self.handle();
+        let key = Arc::as_ptr(&handle.control) as usize;
+        timer::abort::listen(key, signal, handle);
+        self
+    }
+
     fn _reset(&mut self, at: Instant) -> Result<(), ()> {
         let state = match self.state {
             Some(ref state) => state,
@@ -138,7 +498,7 @@ impl Delay {
                     Err(s) => bits = s,
                 }
             }
-            *state.at.lock().unwrap() = Some(at);
+            *timer::lock(&state.at) = Some(at);
             // If we fail to push our node then we've become an inert timer, so
             // we'll want to clear our `state` field accordingly
             timeouts.list.push(state)?;
@@ -149,20 +509,227 @@ impl Delay {
     }
 }
 
+/// State shared between a `Delay` and its `DelayHandle`s.
+#[derive(Debug)]
+struct Control {
+    cancelled: AtomicBool,
+    /// Deadline set through `DelayHandle::reset_at`, which the delay applies
+    /// the next time it's polled.
+    reset: Mutex<Option<Instant>>,
+    /// Whether the delay has resolved or has been dropped, after which handles
+    /// do nothing.
+    done: AtomicBool,
+    waker: AtomicWaker,
+}
+
+/// Handle to a `Delay`, created with `Delay::handle`, which can cancel or
+/// reset it from another task.
+///
+/// Once the delay has resolved, either because it fired or because it was
+/// cancelled, or once it has been dropped, the methods of the handle do
+/// nothing.
+#[derive(Clone, Debug)]
+pub struct DelayHandle {
+    control: Arc<Control>,
+}
+
+impl DelayHandle {
+    /// Cancels the delay, which then resolves immediately with a
+    /// `DelayCancelled` error.
+    pub fn cancel(&self) {
+        if !self.control.done.load(SeqCst) {
+            self.control.cancelled.store(true, SeqCst);
+            self.control.waker.wake();
+        }
+    }
+
+    /// Returns true once the delay has resolved or has been dropped.
+    pub(crate) fn is_done(&self) -> bool {
+        self.control.done.load(SeqCst)
+    }
+
+    /// Resets the delay to fire `dur` from now.
+    pub fn reset(&self, dur: Duration) {
+        self.reset_at(timer::saturating_add(timer::now(), dur))
+    }
+
+    /// Resets the delay to fire at `at`, like `Delay::reset_at`.
+    pub fn reset_at(&self, at: Instant) {
+        if !self.control.done.load(SeqCst) {
+            *timer::lock(&self.control.reset) = Some(at);
+            self.control.waker.wake();
+        }
+    }
+}
+
+/// Waits until `dur` has elapsed.
+///
+/// This is the same as `Delay::new`, except that the returned future resolves
+/// to `()` rather than an `io::Result<()>`. The only way for a `Delay` to fail
+/// is for its timer to be gone, which doesn't happen with the default timer
+/// unless it was explicitly shut down, for example with
+/// `GlobalTimerHandle::cancel_all`.
+///
+/// # Panics
+///
+/// The returned future panics if the timer is gone.
+pub fn sleep(dur: Duration) -> impl Future<Output = ()> {
+    Delay::new(dur).map(|res| res.expect("timer has gone away"))
+}
+
+/// Waits until `at`.
+///
+/// This is the same as `Delay::new_at`. Contrary to `sleep`, the returned
+/// future resolves to an `io::Result<()>`, which is an error if the timer is
+/// gone.
+pub fn sleep_until(at: Instant) -> Sleep {
+    Delay::new_at(at)
+}
+
+/// Waits until `ms` milliseconds have elapsed.
+///
+/// This is a shorthand for `sleep(Duration::from_millis(ms))`, and panics in
+/// the same conditions.
+pub fn sleep_ms(ms: u64) -> impl Future<Output = ()> {
+    sleep(Duration::from_millis(ms))
+}
+
+/// Waits until `secs` seconds have elapsed.
+///
+/// This is a shorthand for `sleep(Duration::from_secs(secs))`, and panics in
+/// the same conditions.
+pub fn sleep_secs(secs: u64) -> impl Future<Output = ()> {
+    sleep(Duration::from_secs(secs))
+}
+
+/// Waits until `us` microseconds have elapsed.
+///
+/// This is a shorthand for `sleep(Duration::from_micros(us))`, and panics in
+/// the same conditions. Note that on WASM, deadlines are rounded down to the
+/// millisecond.
+pub fn sleep_micros(us: u64) -> impl Future<Output = ()> {
+    sleep(Duration::from_micros(us))
+}
+
 #[inline]
 pub fn fires_at(timeout: &Delay) -> Instant {
     timeout.when
 }
 
+/// Returns the identifier of `timeout` in events, if it's bound to a timer.
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub fn id(timeout: &Delay) -> Option<u64> {
+    timeout.state.as_ref().map(|state| state.id)
+}
+
 impl Future for Delay {
     type Output = io::Result<()>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.terminated {
+            return Poll::Pending;
+        }
+        if let Some(control) = this.control.clone() {
+            control.waker.register(cx.waker());
+            if control.cancelled.load(SeqCst) {
+                this.deregister();
+                this.terminated = true;
+                control.done.store(true, SeqCst);
+                return Poll::Ready(Err(DelayCancelled(()).into()));
+            }
+            let reset = timer::lock(&control.reset).take();
+            if let Some(at) = reset {
+                this.reset_at(at);
+            }
+        }
+        let mut ret = this.poll_inner(cx);
+        // Hops towards the wall clock deadline until it's reached.
+        while let Poll::Ready(Ok(())) = ret {
+            let remaining = match this.wall {
+                Some(ref wall) => wall.remaining(),
+                None => break,
+            };
+            if remaining == Duration::ZERO {
+                break;
+            }
+            this.rearm(timer::saturating_add(
+                timer::now(),
+                cmp::min(remaining, WALL_CLOCK_HOP),
+            ));
+            ret = this.poll_inner(cx);
+        }
+        match ret {
+            Poll::Pending => this.yielded = true,
+            Poll::Ready(_) if this.cooperative && !this.yielded => {
+                this.yielded = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(_) => this.yielded = false,
+        }
+        if ret.is_ready() {
+            this.terminated = true;
+            if let Some(ref control) = this.control {
+                control.done.store(true, SeqCst);
+            }
+        }
+        if let Poll::Ready(Ok(())) = ret {
+            this.fired_at = Some(this.node_fired_at().unwrap_or_else(timer::now));
+        }
+        ret
+    }
+}
+
+impl FusedFuture for Delay {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl Delay {
+    /// Returns when the timer stamped this delay as fired, which isn't the
+    /// case of delays backed by `tokio::time::Sleep`.
+    fn node_fired_at(&self) -> Option<Instant> {
+        let state = self.state.as_ref()?;
+        *timer::lock(&state.fired_at)
+    }
+
+    /// Removes this delay from its timer, accounting for it as cancelled if
+    /// it's still pending.
+    fn deregister(&mut self) {
+        let state = match self.state {
+            Some(ref s) => s,
+            None => return,
+        };
+        if let Some(timeouts) = state.inner.upgrade() {
+            // Already removed, e.g. when cancelled through a `DelayHandle`.
+            if timer::lock(&state.at).take().is_none() {
+                return;
+            }
+            // Neither fired nor invalidated, so this is removed while pending.
+            if state.state.load(SeqCst) & 0b11 == 0 {
+                timeouts.total_cancelled.fetch_add(1, SeqCst);
+            }
+            if timeouts.list.push(state).is_ok() {
+                timeouts.waker.wake();
+            }
+        }
+    }
+
+    fn poll_inner(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        #[cfg(all(
+            feature = "tokio",
+            not(all(target_arch = "wasm32", target_os = "unknown"))
+        ))]
+        if let Some(ref mut sleep) = self.sleep {
+            return sleep.as_mut().poll(cx).map(Ok);
+        }
+
         let state = match self.state {
             Some(ref state) => state,
             None => {
-                let err = Err(io::Error::new(io::ErrorKind::Other, "timer has gone away"));
-                return Poll::Ready(err);
+                return Poll::Ready(Err(TimerDropped(()).into()));
             }
         };
 
@@ -177,10 +744,7 @@ impl Future for Delay {
         // invalidated the second bit is set.
         match state.state.load(SeqCst) {
             n if n & 0b01 != 0 => Poll::Ready(Ok(())),
-            n if n & 0b10 != 0 => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::Other,
-                "timer has gone away",
-            ))),
+            n if n & 0b10 != 0 => Poll::Ready(Err(TimerDropped(()).into())),
             _ => Poll::Pending,
         }
     }
@@ -188,15 +752,11 @@ impl Future for Delay {
 
 impl Drop for Delay {
     fn drop(&mut self) {
-        let state = match self.state {
-            Some(ref s) => s,
-            None => return,
-        };
-        if let Some(timeouts) = state.inner.upgrade() {
-            *state.at.lock().unwrap() = None;
-            if timeouts.list.push(state).is_ok() {
-                timeouts.waker.wake();
-            }
+        self.deregister();
+        if let Some(ref control) = self.control {
+            control.done.store(true, SeqCst);
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+            timer::abort::unlisten(Arc::as_ptr(control) as usize);
         }
     }
 }
@@ -206,3 +766,462 @@ impl fmt::Debug for Delay {
         f.debug_struct("Delay").field("when", &self.when).finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::future::FusedFuture;
+    use futures::prelude::*;
+    use futures::stream::FusedStream;
+    use futures::task::{self, noop_waker, ArcWake};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{sleep, sleep_micros, sleep_ms, sleep_secs, sleep_until, Sleep};
+    use crate::{
+        Delay, DelayCancelled, Instant, Interval, SystemTime, Ticker, Timer, TimerDropped,
+        TryFutureExt, UNIX_EPOCH,
+    };
+
+    fn is_timer_dropped(poll: Poll<io::Result<()>>) -> bool {
+        match poll {
+            Poll::Ready(Err(err)) => err.get_ref().is_some_and(|err| err.is::<TimerDropped>()),
+            _ => false,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn dropping_timer_fails_delays() {
+        let mut timer = Timer::new();
+        let at = Instant::now() + Duration::from_secs(3600);
+        let mut scheduled = Delay::new_handle(at, timer.handle());
+        let _ = Pin::new(&mut timer).poll(&mut Context::from_waker(&noop_waker()));
+        let mut queued = Delay::new_handle(at, timer.handle());
+        let mut interval = Interval::new_handle(at, Duration::from_secs(1), timer.handle());
+        let handle = timer.handle();
+        let mut timeout = {
+            let _guard = handle.set_default();
+            future::pending::<io::Result<()>>().timeout_at(at)
+        };
+        drop(timer);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(is_timer_dropped(scheduled.poll_unpin(&mut cx)));
+        assert!(is_timer_dropped(queued.poll_unpin(&mut cx)));
+        assert!(is_timer_dropped(
+            Delay::new_handle(at, handle).poll_unpin(&mut cx)
+        ));
+        assert!(is_timer_dropped(timeout.poll_unpin(&mut cx)));
+        assert_eq!(interval.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert!(interval.is_terminated());
+    }
+
+    #[wasm_bindgen_test]
+    fn new_or_panic() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let start = Instant::now();
+        let delay = Delay::new_or_panic(Duration::from_secs(1));
+        assert!(delay.deadline() >= start + Duration::from_secs(1));
+        // `Delay::new` clamps the deadline instead.
+        assert!(Delay::new(Duration::MAX).deadline() > start + Duration::from_secs(3600));
+    }
+
+    #[wasm_bindgen_test]
+    #[should_panic(expected = "overflows an Instant")]
+    fn new_or_panic_overflow() {
+        Delay::new_or_panic(Duration::MAX);
+    }
+
+    #[wasm_bindgen_test]
+    fn new_at_system_follows_clock() {
+        thread_local! {
+            static WALL_NOW: Cell<SystemTime> = const { Cell::new(UNIX_EPOCH) };
+        }
+        fn wall_now() -> SystemTime {
+            WALL_NOW.with(Cell::get)
+        }
+        fn set_wall_now(secs: u64) {
+            WALL_NOW.with(|now| now.set(UNIX_EPOCH + Duration::from_secs(secs)));
+        }
+
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now();
+        let when = UNIX_EPOCH + Duration::from_secs(100);
+
+        // The first hop is capped at a minute.
+        set_wall_now(0);
+        let mut delay = Delay::new_at_system_with_clock(when, wall_now);
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert!(delay.deadline() <= Instant::now() + Duration::from_secs(60));
+
+        // The clock stepped backward, so another hop is needed.
+        set_wall_now(30);
+        timer.fire_expired(start + Duration::from_secs(61));
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+
+        // The clock jumped forward past the deadline.
+        set_wall_now(101);
+        timer.fire_expired(start + Duration::from_secs(122));
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+
+        // Deadlines in the past fire right away.
+        let mut past = Delay::new_at_system_with_clock(when, wall_now);
+        timer.fire_expired(Instant::now());
+        assert!(matches!(past.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+    }
+
+    #[wasm_bindgen_test]
+    async fn sleep_completes() {
+        let start = Instant::now();
+        let () = sleep(Duration::from_millis(10)).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[wasm_bindgen_test]
+    async fn sleep_ms_completes() {
+        let start = Instant::now();
+        sleep_ms(10).await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[wasm_bindgen_test]
+    async fn sleep_until_completes() {
+        let at = Instant::now() + Duration::from_millis(10);
+        let sleep: Sleep = sleep_until(at);
+        assert_eq!(sleep.deadline(), Delay::new_at(at).deadline());
+        sleep.await.unwrap();
+        assert!(Instant::now() >= at);
+    }
+
+    #[wasm_bindgen_test]
+    async fn sleep_new_completes() {
+        let start = Instant::now();
+        Sleep::new(Duration::from_millis(10)).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[wasm_bindgen_test]
+    async fn sleep_secs_completes() {
+        let start = Instant::now();
+        sleep_secs(1).await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[wasm_bindgen_test]
+    async fn sleep_micros_completes() {
+        let start = Instant::now();
+        sleep_micros(2_000).await;
+        assert!(start.elapsed() >= Duration::from_micros(2_000));
+    }
+
+    #[wasm_bindgen_test]
+    fn completed_delay_is_fused() {
+        let mut timer = Timer::new();
+        let at = Instant::now() + Duration::from_secs(3600);
+        let mut delay = Delay::new_handle(at, timer.handle());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert!(!delay.is_terminated());
+        let _ = Pin::new(&mut timer).poll(&mut cx);
+        timer.advance_to(at);
+        timer.advance_to(at);
+
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        assert!(delay.is_terminated());
+        for _ in 0..3 {
+            assert!(delay.poll_unpin(&mut cx).is_pending());
+        }
+        assert!(delay.is_terminated());
+
+        delay.reset_at(at);
+        assert!(!delay.is_terminated());
+        let _ = Pin::new(&mut timer).poll(&mut cx);
+        timer.advance_to(at);
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+    }
+
+    #[wasm_bindgen_test]
+    fn max_duration_does_not_overflow() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let _guard = handle.set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut delay = Delay::new(Duration::MAX);
+        delay.reset(Duration::MAX);
+        let mut from_handle = handle.delay(Duration::MAX);
+        let mut interval = Interval::new(Duration::MAX);
+        let mut from_handle_interval = handle.interval(Duration::MAX);
+        let mut timeout = future::pending::<io::Result<()>>().timeout(Duration::MAX);
+        let mut once = Ticker::once(Duration::MAX);
+        let mut repeating = Ticker::repeating(Duration::MAX);
+
+        assert_eq!(timer.fire_expired(Instant::now()), 0);
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert!(from_handle.poll_unpin(&mut cx).is_pending());
+        assert!(interval.poll_next_unpin(&mut cx).is_pending());
+        assert!(from_handle_interval.poll_next_unpin(&mut cx).is_pending());
+        assert!(timeout.poll_unpin(&mut cx).is_pending());
+        assert!(once.poll_next_unpin(&mut cx).is_pending());
+        assert!(repeating.poll_next_unpin(&mut cx).is_pending());
+    }
+
+    #[wasm_bindgen_test]
+    fn lateness() {
+        let mut timer = Timer::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let at = Instant::now() + Duration::from_secs(3600);
+
+        let mut prompt = Delay::new_handle(at, timer.handle());
+        assert_eq!(prompt.fired_at(), None);
+        assert_eq!(prompt.lateness(), None);
+        assert_eq!(timer.fire_expired(at), 1);
+        assert!(matches!(prompt.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(prompt.fired_at(), Some(at));
+        assert_eq!(prompt.lateness(), Some(Duration::ZERO));
+
+        let mut late = Delay::new_handle(at, timer.handle());
+        let now = at + Duration::from_secs(5);
+        assert_eq!(timer.fire_expired(now), 1);
+        assert!(matches!(late.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(late.fired_at(), Some(now));
+        assert_eq!(late.lateness(), Some(Duration::from_secs(5)));
+
+        assert_eq!(late.deadline(), at);
+        late.reset_at(now + Duration::from_secs(1));
+        assert_eq!(late.deadline(), now + Duration::from_secs(1));
+        assert_eq!(late.lateness(), None);
+    }
+
+    /// Waker of a task, recording whether it was woken.
+    struct Task(AtomicBool);
+
+    impl ArcWake for Task {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.store(true, SeqCst);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn polled_from_another_task() {
+        let mut timer = Timer::new();
+        let now = Instant::now() + Duration::from_secs(3600);
+        let first = Arc::new(Task(AtomicBool::new(false)));
+        let second = Arc::new(Task(AtomicBool::new(false)));
+        let first_waker = task::waker(first.clone());
+        let second_waker = task::waker(second.clone());
+        let mut first_cx = Context::from_waker(&first_waker);
+        let mut second_cx = Context::from_waker(&second_waker);
+
+        // The delay is already in the heap when it moves to the second task.
+        let mut delay = Delay::new_handle(now, timer.handle());
+        assert!(delay.poll_unpin(&mut first_cx).is_pending());
+        assert_eq!(timer.fire_expired(now - Duration::from_secs(1)), 0);
+        assert!(delay.poll_unpin(&mut second_cx).is_pending());
+        assert_eq!(timer.fire_expired(now), 1);
+        assert!(!first.0.swap(false, SeqCst));
+        assert!(second.0.swap(false, SeqCst));
+
+        // Same for an interval, between two of its ticks.
+        let mut interval = Interval::new_handle(now, Duration::from_secs(1), timer.handle());
+        assert!(interval.poll_tick(&mut first_cx).is_pending());
+        timer.fire_expired(now);
+        assert!(interval.poll_tick(&mut first_cx).is_ready());
+        assert!(interval.poll_tick(&mut first_cx).is_pending());
+        first.0.store(false, SeqCst);
+        assert!(interval.poll_tick(&mut second_cx).is_pending());
+        timer.fire_expired(now + Duration::from_secs(1));
+        assert!(!first.0.load(SeqCst));
+        assert!(second.0.load(SeqCst));
+    }
+
+    #[cfg(all(
+        feature = "tokio",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    ))]
+    #[test]
+    fn try_new_outside_runtime() {
+        assert!(Delay::try_new(Duration::from_secs(1)).is_err());
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    #[test]
+    fn new_at_std() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let at = std::time::Instant::now() + Duration::from_secs(5);
+        // Outside of WASM, the two types are the same.
+        let same: Instant = at;
+        assert_eq!(Delay::new_at_std(at).deadline(), same);
+    }
+
+    #[wasm_bindgen_test]
+    fn cooperative_yields_once() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let task = Arc::new(Task(AtomicBool::new(false)));
+        let waker = task::waker(task.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut delay = Delay::new_cooperative(Duration::ZERO);
+        timer.fire_expired(Instant::now());
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert!(task.0.swap(false, SeqCst));
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+
+        // Once reset, it yields again.
+        delay.reset_at(Instant::now());
+        timer.fire_expired(Instant::now());
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+
+        // It doesn't yield twice if it wasn't ready when first polled.
+        delay.reset_at(Instant::now() + Duration::from_secs(1));
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        timer.fire_expired(Instant::now() + Duration::from_secs(1));
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+    }
+
+    fn is_cancelled(poll: Poll<io::Result<()>>) -> bool {
+        match poll {
+            Poll::Ready(Err(err)) => err.get_ref().is_some_and(|err| err.is::<DelayCancelled>()),
+            _ => false,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn try_new() {
+        let timer = Timer::new();
+        let handle = timer.handle();
+        let guard = handle.set_default();
+        assert!(Delay::try_new(Duration::from_secs(1)).is_ok());
+        assert!(Interval::try_new(Duration::from_secs(1)).is_ok());
+        drop(guard);
+
+        // Simulates a timer backend which couldn't be started.
+        drop(timer);
+        let _guard = handle.set_default();
+        let err = Delay::try_new(Duration::from_secs(1)).unwrap_err();
+        assert!(err.get_ref().is_some_and(|err| err.is::<TimerDropped>()));
+        assert!(Interval::try_new(Duration::from_secs(1)).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn delay_handle() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let at = Instant::now() + Duration::from_secs(3600);
+
+        let mut cancelled = Delay::new_handle(at, timer.handle());
+        let control = cancelled.handle();
+        assert!(cancelled.poll_unpin(&mut cx).is_pending());
+        control.clone().cancel();
+        assert!(is_cancelled(cancelled.poll_unpin(&mut cx)));
+        assert!(cancelled.is_terminated());
+        assert_eq!(timer.fire_expired(at), 0);
+        assert_eq!(handle.stats().unwrap().total_cancelled, 1);
+        drop(cancelled);
+        assert_eq!(handle.stats().unwrap().total_cancelled, 1);
+
+        let mut reset = Delay::new_handle(at, timer.handle());
+        let control = reset.handle();
+        control.reset_at(at + Duration::from_secs(10));
+        assert!(reset.poll_unpin(&mut cx).is_pending());
+        assert_eq!(timer.fire_expired(at), 0);
+        assert!(reset.poll_unpin(&mut cx).is_pending());
+        assert_eq!(timer.fire_expired(at + Duration::from_secs(10)), 1);
+        assert!(matches!(reset.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+
+        // Handles do nothing once the delay has resolved.
+        control.cancel();
+        control.reset_at(at);
+        assert!(reset.poll_unpin(&mut cx).is_pending());
+        reset.reset_at(at + Duration::from_secs(20));
+        assert!(reset.poll_unpin(&mut cx).is_pending());
+        assert_eq!(timer.fire_expired(at + Duration::from_secs(20)), 1);
+        assert!(matches!(reset.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    #[wasm_bindgen_test]
+    async fn cancel_from_other_task() {
+        let start = Instant::now();
+        let mut delay = Delay::new(Duration::from_secs(3600));
+        let handle = delay.handle();
+        crate::spawn_after(Duration::from_millis(10), move || handle.cancel()).forget();
+        let err = delay.await.unwrap_err();
+        assert!(err.get_ref().is_some_and(|err| err.is::<DelayCancelled>()));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "tokio",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+mod tokio_tests {
+    use std::io;
+    use std::time::Duration;
+
+    use futures::prelude::*;
+
+    use crate::{Delay, Interval, TryFutureExt};
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_follows_paused_clock() {
+        let start = tokio::time::Instant::now();
+        Delay::new(Duration::from_secs(3600)).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(3600));
+        assert!(start.elapsed() < Duration::from_secs(3601));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_at_moves_deadline() {
+        let start = tokio::time::Instant::now();
+        let mut delay = Delay::new(Duration::from_secs(10));
+        delay.reset_at(start.into_std() + Duration::from_secs(60));
+        delay.await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(60));
+        assert!(start.elapsed() < Duration::from_secs(61));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn interval_follows_paused_clock() {
+        let start = tokio::time::Instant::now();
+        let mut interval = Interval::new(Duration::from_secs(60));
+        interval.next().await;
+        interval.next().await;
+        assert!(start.elapsed() >= Duration::from_secs(120));
+        assert!(start.elapsed() < Duration::from_secs(121));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_follows_paused_clock() {
+        let start = tokio::time::Instant::now();
+        let err = future::pending::<io::Result<()>>()
+            .timeout(Duration::from_secs(30))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_secs(30));
+    }
+}
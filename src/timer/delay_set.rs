@@ -0,0 +1,296 @@
+//! A set of keys, each of which expires at its own deadline.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::future::FusedFuture;
+use futures::prelude::*;
+
+use crate::timer::heap::{Heap, Slot};
+use crate::timer::{self, delay};
+use crate::{Delay, Instant};
+
+/// A set of keys with deadlines, which yields each key once its deadline has
+/// elapsed.
+///
+/// Contrary to driving one `Delay` per key, e.g. through `FuturesUnordered`,
+/// the keys are kept in a heap ordered by deadline and a single `Delay` is
+/// armed, for the earliest one. This scales to the many keys of e.g. a
+/// connection manager tracking idle timeouts.
+///
+/// Keys sharing a deadline are yielded in the order they were inserted or
+/// reset in. When the set is empty, the stream is pending until a key is
+/// inserted, and it only ends if its timer is gone.
+pub struct DelaySet<K> {
+    /// Keys keyed by deadline, then by insertion order.
+    heap: Heap<(Instant, u64), K>,
+    slots: HashMap<K, Slot>,
+    /// Delay armed for the earliest deadline, if there's any key.
+    delay: Option<Delay>,
+    /// Deadline of the last time `delay` fired, up to which keys are expired.
+    expired_until: Option<Instant>,
+    next_seq: u64,
+    /// Task to wake when the earliest deadline changes.
+    waker: Option<Waker>,
+}
+
+impl<K: Eq + Hash + Clone> DelaySet<K> {
+    /// Creates an empty set, whose delay is bound to the default timer.
+    pub fn new() -> DelaySet<K> {
+        DelaySet {
+            heap: Heap::new(),
+            slots: HashMap::new(),
+            delay: None,
+            expired_until: None,
+            next_seq: 0,
+            waker: None,
+        }
+    }
+
+    /// Inserts `key`, to be yielded once `dur` has elapsed.
+    ///
+    /// If the set already contains `key`, its deadline is replaced.
+    pub fn insert(&mut self, key: K, dur: Duration) {
+        self.insert_at(key, timer::saturating_add(timer::now(), dur))
+    }
+
+    /// Inserts `key`, to be yielded at `at`.
+    ///
+    /// If the set already contains `key`, its deadline is replaced.
+    pub fn insert_at(&mut self, key: K, at: Instant) {
+        if let Some(slot) = self.slots.remove(&key) {
+            self.heap.remove(slot);
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let slot = self.heap.push((at, seq), key.clone());
+        self.slots.insert(key, slot);
+        self.rearm();
+    }
+
+    /// Removes `key`, and returns whether the set contained it.
+    pub fn remove(&mut self, key: &K) -> bool {
+        let slot = match self.slots.remove(key) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        self.heap.remove(slot);
+        self.rearm();
+        true
+    }
+
+    /// Moves the deadline of `key` to `dur` from now, and returns whether the
+    /// set contained it. Nothing is inserted if it didn't.
+    pub fn reset(&mut self, key: &K, dur: Duration) -> bool {
+        if !self.slots.contains_key(key) {
+            return false;
+        }
+        self.insert(key.clone(), dur);
+        true
+    }
+
+    /// Returns the deadline of `key`, if the set contains it.
+    pub fn deadline(&self, key: &K) -> Option<Instant> {
+        self.slots.get(key).map(|slot| self.heap.get(slot).0 .0)
+    }
+
+    /// Returns whether the set contains `key`.
+    pub fn contains(&self, key: &K) -> bool {
+        self.slots.contains_key(key)
+    }
+
+    /// Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Makes the delay fire at the earliest deadline, dropping it if the set
+    /// is empty, and wakes the task if it was changed.
+    fn rearm(&mut self) {
+        let at = match self.heap.peek() {
+            Some((&(at, _), _)) => at,
+            None => {
+                self.delay = None;
+                return;
+            }
+        };
+        match self.delay {
+            Some(ref delay) if delay::fires_at(delay) == at && !delay.is_terminated() => return,
+            Some(ref mut delay) => delay.reset_at(at),
+            None => self.delay = Some(Delay::new_at(at)),
+        }
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Removes the earliest key and returns it.
+    fn pop(&mut self) -> K {
+        let (_, key) = self.heap.pop().unwrap();
+        self.slots.remove(&key);
+        key
+    }
+}
+
+impl<K: Eq + Hash + Clone + Unpin> Stream for DelaySet<K> {
+    type Item = K;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<K>> {
+        let this = self.get_mut();
+        let at = match this.heap.peek() {
+            Some((&(at, _), _)) => at,
+            None => {
+                this.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        };
+        if this.expired_until.is_some_and(|until| at <= until) || at <= timer::now() {
+            return Poll::Ready(Some(this.pop()));
+        }
+        this.rearm();
+        this.waker = Some(cx.waker().clone());
+        let delay = this.delay.as_mut().unwrap();
+        match Pin::new(&mut *delay).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(())) => {
+                this.expired_until = Some(delay::fires_at(delay));
+                Poll::Ready(Some(this.pop()))
+            }
+            // The timer is gone, so no key will ever expire.
+            Poll::Ready(Err(_)) => Poll::Ready(None),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for DelaySet<K> {
+    fn default() -> DelaySet<K> {
+        DelaySet::new()
+    }
+}
+
+impl<K> fmt::Debug for DelaySet<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DelaySet")
+            .field("len", &self.slots.len())
+            .field("next_deadline", &self.heap.peek().map(|(&(at, _), _)| at))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::DelaySet;
+    use crate::{Instant, Timer};
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[wasm_bindgen_test]
+    fn yields_keys_in_deadline_order() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now() + secs(3600);
+
+        let mut set = DelaySet::new();
+        set.insert_at("a", start + secs(30));
+        set.insert_at("b", start + secs(10));
+        set.insert_at("c", start + secs(20));
+        set.insert_at("d", start + secs(20));
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+
+        timer.fire_expired(start + secs(20));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some("b")));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+        timer.fire_expired(start + secs(20));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some("c")));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some("d")));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+        timer.fire_expired(start + secs(30));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some("a")));
+        assert!(set.is_empty());
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+    }
+
+    #[wasm_bindgen_test]
+    fn reset_moves_keys() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now() + secs(3600);
+
+        let mut set = DelaySet::new();
+        set.insert_at(1, start + secs(10));
+        set.insert_at(2, start + secs(20));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+
+        // Later: the other key now expires first.
+        set.insert_at(1, start + secs(30));
+        timer.fire_expired(start + secs(20));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some(2)));
+
+        // Earlier, relative to now.
+        assert!(set.reset(&1, Duration::ZERO));
+        assert!(set.deadline(&1).unwrap() < start);
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some(1)));
+        assert!(!set.reset(&1, secs(1)));
+        assert!(set.is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn removing_earliest_key_rearms() {
+        let mut timer = Timer::new();
+        let handle = timer.handle();
+        let _guard = handle.set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now() + secs(3600);
+
+        let mut set = DelaySet::new();
+        set.insert_at("a", start + secs(10));
+        set.insert_at("b", start + secs(20));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+        timer.fire_expired(start);
+        assert_eq!(
+            handle.stats().unwrap().next_deadline,
+            Some(start + secs(10))
+        );
+
+        assert!(set.remove(&"a"));
+        assert!(!set.remove(&"a"));
+        timer.fire_expired(start);
+        assert_eq!(
+            handle.stats().unwrap().next_deadline,
+            Some(start + secs(20))
+        );
+        timer.fire_expired(start + secs(10));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Pending);
+        timer.fire_expired(start + secs(20));
+        assert_eq!(set.poll_next_unpin(&mut cx), Poll::Ready(Some("b")));
+
+        set.insert_at("c", start + secs(30));
+        assert!(set.remove(&"c"));
+        assert!(set.delay.is_none());
+    }
+}
@@ -0,0 +1,218 @@
+//! Errors of the timer.
+//!
+//! Futures of this crate resolve to `io::Error`s, for compatibility, which
+//! wrap one of the types of this module. `TimerError::from_io_error` finds
+//! which one, e.g. to convert them to the errors of an application.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Error returned when a timeout elapses.
+///
+/// It converts to an `io::Error` of kind `TimedOut`, which is what `Timeout`
+/// and `TimeoutStream` resolve to in this case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Elapsed(pub(crate) ());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+impl From<Elapsed> for io::Error {
+    fn from(err: Elapsed) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+    }
+}
+
+/// Error returned by a `Delay` whose `Timer` is gone, or which was cancelled
+/// through `TimerHandle::cancel_all`.
+///
+/// `Delay` resolves to an `io::Error`, which wraps this type so that the
+/// condition can be told apart from other errors:
+///
+/// ```
+/// use std::io;
+/// use zduny_wasm_timer::TimerDropped;
+///
+/// fn is_timer_dropped(err: &io::Error) -> bool {
+///     err.get_ref().is_some_and(|err| err.is::<TimerDropped>())
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimerDropped(pub(crate) ());
+
+impl fmt::Display for TimerDropped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("timer has gone away")
+    }
+}
+
+impl Error for TimerDropped {}
+
+impl From<TimerDropped> for io::Error {
+    fn from(err: TimerDropped) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+/// Error returned by a `Delay` which was cancelled through a `DelayHandle`.
+///
+/// Like `TimerDropped`, it's wrapped in the `io::Error` the `Delay` resolves
+/// to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelayCancelled(pub(crate) ());
+
+impl fmt::Display for DelayCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("delay was cancelled")
+    }
+}
+
+impl Error for DelayCancelled {}
+
+impl From<DelayCancelled> for io::Error {
+    fn from(err: DelayCancelled) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+/// Any of the errors of the timer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimerError {
+    /// A timeout elapsed.
+    Elapsed(Elapsed),
+    /// The timer is gone.
+    TimerDropped(TimerDropped),
+    /// A delay was cancelled through a `DelayHandle`.
+    DelayCancelled(DelayCancelled),
+}
+
+impl TimerError {
+    /// Returns the error of the timer wrapped by `err`, if any.
+    ///
+    /// ```
+    /// use std::io;
+    /// use zduny_wasm_timer::TimerError;
+    ///
+    /// # fn example(result: io::Result<()>) {
+    /// match result {
+    ///     Ok(()) => {}
+    ///     Err(err) => match TimerError::from_io_error(&err) {
+    ///         Some(TimerError::Elapsed(_)) => println!("took too long"),
+    ///         Some(err) => println!("timer error: {}", err),
+    ///         None => println!("other error: {}", err),
+    ///     },
+    /// }
+    /// # }
+    /// ```
+    pub fn from_io_error(err: &io::Error) -> Option<TimerError> {
+        let inner = err.get_ref()?;
+        if let Some(err) = inner.downcast_ref::<Elapsed>() {
+            return Some(TimerError::Elapsed(err.clone()));
+        }
+        if let Some(err) = inner.downcast_ref::<TimerDropped>() {
+            return Some(TimerError::TimerDropped(err.clone()));
+        }
+        if let Some(err) = inner.downcast_ref::<DelayCancelled>() {
+            return Some(TimerError::DelayCancelled(err.clone()));
+        }
+        None
+    }
+}
+
+impl fmt::Display for TimerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimerError::Elapsed(err) => err.fmt(f),
+            TimerError::TimerDropped(err) => err.fmt(f),
+            TimerError::DelayCancelled(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for TimerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TimerError::Elapsed(err) => Some(err),
+            TimerError::TimerDropped(err) => Some(err),
+            TimerError::DelayCancelled(err) => Some(err),
+        }
+    }
+}
+
+impl From<Elapsed> for TimerError {
+    fn from(err: Elapsed) -> TimerError {
+        TimerError::Elapsed(err)
+    }
+}
+
+impl From<TimerDropped> for TimerError {
+    fn from(err: TimerDropped) -> TimerError {
+        TimerError::TimerDropped(err)
+    }
+}
+
+impl From<DelayCancelled> for TimerError {
+    fn from(err: DelayCancelled) -> TimerError {
+        TimerError::DelayCancelled(err)
+    }
+}
+
+impl From<TimerError> for io::Error {
+    fn from(err: TimerError) -> io::Error {
+        match err {
+            TimerError::Elapsed(err) => err.into(),
+            TimerError::TimerDropped(err) => err.into(),
+            TimerError::DelayCancelled(err) => err.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{DelayCancelled, Elapsed, TimerDropped, TimerError};
+
+    #[wasm_bindgen_test]
+    fn display() {
+        assert_eq!(Elapsed(()).to_string(), "deadline has elapsed");
+        assert_eq!(TimerDropped(()).to_string(), "timer has gone away");
+        assert_eq!(DelayCancelled(()).to_string(), "delay was cancelled");
+        assert_eq!(
+            TimerError::from(Elapsed(())).to_string(),
+            "deadline has elapsed"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn io_conversions() {
+        let cases = [
+            (TimerError::Elapsed(Elapsed(())), io::ErrorKind::TimedOut),
+            (
+                TimerError::TimerDropped(TimerDropped(())),
+                io::ErrorKind::Other,
+            ),
+            (
+                TimerError::DelayCancelled(DelayCancelled(())),
+                io::ErrorKind::Other,
+            ),
+        ];
+        for (err, kind) in cases {
+            let io_err = io::Error::from(err.clone());
+            assert_eq!(io_err.kind(), kind);
+            assert_eq!(io_err.to_string(), err.to_string());
+            assert_eq!(TimerError::from_io_error(&io_err), Some(err));
+        }
+        let other = io::Error::new(io::ErrorKind::TimedOut, "other");
+        assert_eq!(TimerError::from_io_error(&other), None);
+    }
+}
@@ -1,14 +1,18 @@
 //! Extension traits for the standard `Stream` and `Future` traits.
 
+use std::cmp;
 use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures::prelude::*;
-use pin_utils::unsafe_pinned;
+#[allow(deprecated)]
+use pin_utils::{unsafe_pinned, unsafe_unpinned};
 
-use crate::{Delay, Instant};
+use crate::timer;
+use crate::timer::error::Elapsed;
+use crate::{Delay, Instant, SystemTime};
 
 /// An extension trait for futures which provides convenient accessors for
 /// timing out execution and such.
@@ -29,7 +33,7 @@ pub trait TryFutureExt: TryFuture + Sized {
     /// ```no_run
     /// use std::time::Duration;
     /// use futures::prelude::*;
-    /// use wasm_timer::TryFutureExt;
+    /// use zduny_wasm_timer::TryFutureExt;
     ///
     /// # fn long_future() -> impl TryFuture<Ok = (), Error = std::io::Error> {
     /// #     futures::future::ok(())
@@ -92,8 +96,28 @@ where
     F: TryFuture,
     F::Error: From<io::Error>,
 {
+    #![allow(deprecated)]
     unsafe_pinned!(future: F);
     unsafe_pinned!(timeout: Delay);
+
+    /// Returns the instant this timeout elapses at.
+    pub fn deadline(&self) -> Instant {
+        self.timeout.deadline()
+    }
+
+    /// Cancels this timeout when `signal` aborts.
+    ///
+    /// The timeout then resolves with a `DelayCancelled` error, unless the
+    /// future completes first. See `Delay::with_abort_signal`.
+    ///
+    /// This is only available on WASM.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn with_abort_signal(self, signal: &web_sys::AbortSignal) -> Self {
+        Timeout {
+            future: self.future,
+            timeout: self.timeout.with_abort_signal(signal),
+        }
+    }
 }
 
 impl<F> Future for Timeout<F>
@@ -109,11 +133,15 @@ where
             other => return other,
         }
 
-        if self.timeout().poll(cx).is_ready() {
-            let err = Err(io::Error::new(io::ErrorKind::TimedOut, "future timed out").into());
-            Poll::Ready(err)
-        } else {
-            Poll::Pending
+        match self.timeout().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(())) => {
+                let err = io::Error::from(Elapsed(()));
+                Poll::Ready(Err(err.into()))
+            }
+            // The timer is gone or the timeout was cancelled, which is
+            // reported as is.
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err.into())),
         }
     }
 }
@@ -163,6 +191,7 @@ where
     S: TryStream,
     S::Error: From<io::Error>,
 {
+    #![allow(deprecated)]
     unsafe_pinned!(timeout: Delay);
     unsafe_pinned!(stream: S);
 }
@@ -186,15 +215,581 @@ where
             }
         }
 
-        if self.as_mut().timeout().poll(cx).is_ready() {
-            self.as_mut().timeout().reset(dur);
-            Poll::Ready(Some(Err(io::Error::new(
-                io::ErrorKind::TimedOut,
-                "stream item timed out",
-            )
-            .into())))
+        match self.as_mut().timeout().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(())) => {
+                self.as_mut().timeout().reset(dur);
+                let err = io::Error::from(Elapsed(()));
+                Poll::Ready(Some(Err(err.into())))
+            }
+            // The timer is gone, which is reported as is.
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err.into()))),
+        }
+    }
+}
+
+/// A sink whose back-pressure waits are bounded: if `poll_ready`, or
+/// `poll_flush` and `poll_close`, stay pending for longer than their timeout,
+/// they fail with an `Elapsed` error, converted to the error type of the sink
+/// through `io::Error`.
+///
+/// The timeout is restarted whenever the inner sink makes progress. There is
+/// no timeout by default.
+#[derive(Debug)]
+pub struct TimeoutSink<S> {
+    sink: S,
+    send_timeout: Option<Duration>,
+    flush_timeout: Option<Duration>,
+    /// Delay armed while waiting on the inner sink.
+    timeout: Option<Delay>,
+}
+
+impl<S> TimeoutSink<S> {
+    #![allow(deprecated)]
+    unsafe_pinned!(sink: S);
+    unsafe_unpinned!(timeout: Option<Delay>);
+
+    /// Wraps `sink`, without any timeout.
+    pub fn new(sink: S) -> TimeoutSink<S> {
+        TimeoutSink {
+            sink,
+            send_timeout: None,
+            flush_timeout: None,
+            timeout: None,
+        }
+    }
+
+    /// Sets how long `poll_ready` may stay pending, or removes the timeout
+    /// if `None`. This applies from the next wait on.
+    pub fn set_send_timeout(&mut self, timeout: Option<Duration>) {
+        self.send_timeout = timeout;
+    }
+
+    /// Sets how long `poll_flush` and `poll_close` may stay pending, or
+    /// removes the timeout if `None`. This applies from the next wait on.
+    pub fn set_flush_timeout(&mut self, timeout: Option<Duration>) {
+        self.flush_timeout = timeout;
+    }
+
+    /// Returns the inner sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    /// Polls the inner sink through `poll`, failing if it's pending for
+    /// longer than `timeout`.
+    fn poll_timed<E>(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        timeout: Option<Duration>,
+        poll: impl FnOnce(Pin<&mut S>, &mut Context<'_>) -> Poll<Result<(), E>>,
+    ) -> Poll<Result<(), E>>
+    where
+        E: From<io::Error>,
+    {
+        if let Poll::Ready(res) = poll(self.as_mut().sink(), cx) {
+            *self.timeout() = None;
+            return Poll::Ready(res);
+        }
+        let dur = match timeout {
+            Some(dur) => dur,
+            None => return Poll::Pending,
+        };
+        let timeout = self.timeout();
+        let res = match timeout
+            .get_or_insert_with(|| Delay::new(dur))
+            .poll_unpin(cx)
+        {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(res) => res,
+        };
+        *timeout = None;
+        // The timer being gone is reported as is.
+        let err = res.err().unwrap_or_else(|| Elapsed(()).into());
+        Poll::Ready(Err(err.into()))
+    }
+}
+
+impl<S, Item> Sink<Item> for TimeoutSink<S>
+where
+    S: Sink<Item>,
+    S::Error: From<io::Error>,
+{
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        let timeout = self.send_timeout;
+        self.poll_timed(cx, timeout, |sink, cx| sink.poll_ready(cx))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), S::Error> {
+        self.sink().start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        let timeout = self.flush_timeout;
+        self.poll_timed(cx, timeout, |sink, cx| sink.poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        let timeout = self.flush_timeout;
+        self.poll_timed(cx, timeout, |sink, cx| sink.poll_close(cx))
+    }
+}
+
+/// An extension trait for streams of instants at which items were scheduled,
+/// such as `Interval::ticks`.
+pub trait ScheduledStreamExt: Stream<Item = Instant> + Sized {
+    /// Creates a new stream which yields each instant along with the instant
+    /// it was actually delivered at, e.g. to log the jitter of an interval
+    /// whose consumer does heavy work.
+    fn with_schedule_times(self) -> ScheduledAtStream<Self> {
+        ScheduledAtStream { stream: self }
+    }
+}
+
+impl<S: Stream<Item = Instant>> ScheduledStreamExt for S {}
+
+/// A value tagged with the instant it was scheduled for, and the instant it
+/// was actually delivered at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScheduledAt<T> {
+    /// The tagged value.
+    pub value: T,
+    /// The instant the value was scheduled for.
+    pub scheduled: Instant,
+    /// The instant the value was delivered at.
+    pub actual: Instant,
+}
+
+impl<T> ScheduledAt<T> {
+    /// Returns how late the value was delivered, or zero if it was delivered
+    /// early.
+    pub fn jitter(&self) -> Duration {
+        timer::lateness(self.scheduled, self.actual)
+    }
+}
+
+/// Stream returned by the `ScheduledStreamExt::with_schedule_times` method.
+#[derive(Debug)]
+pub struct ScheduledAtStream<S> {
+    stream: S,
+}
+
+impl<S> ScheduledAtStream<S> {
+    #![allow(deprecated)]
+    unsafe_pinned!(stream: S);
+}
+
+impl<S> Stream for ScheduledAtStream<S>
+where
+    S: Stream<Item = Instant>,
+{
+    type Item = ScheduledAt<Instant>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream().poll_next(cx).map(|scheduled| {
+            scheduled.map(|scheduled| ScheduledAt {
+                value: scheduled,
+                scheduled,
+                actual: timer::now(),
+            })
+        })
+    }
+}
+
+/// Creates a new future which waits for `future` for at most `dur` time.
+///
+/// This is the same as `TryFutureExt::timeout`, with the argument order of
+/// `tokio::time::timeout`.
+pub fn timeout<F>(dur: Duration, future: F) -> Timeout<F>
+where
+    F: TryFuture,
+    F::Error: From<io::Error>,
+{
+    future.timeout(dur)
+}
+
+/// Creates a new future which waits for `future` until `at` at most.
+///
+/// This is the same as `TryFutureExt::timeout_at`, with the argument order of
+/// `tokio::time::timeout_at`.
+pub fn timeout_at<F>(at: Instant, future: F) -> Timeout<F>
+where
+    F: TryFuture,
+    F::Error: From<io::Error>,
+{
+    future.timeout_at(at)
+}
+
+/// Creates a new future which waits for `future` for at most `dur` time, and
+/// gives it back if it doesn't complete in time.
+///
+/// Contrary to `TryFutureExt::timeout`, the future isn't dropped when the
+/// timeout elapses, which matters for futures that aren't safe to cancel: the
+/// caller gets it back along with an `Elapsed` error, and may for example
+/// resume awaiting it later. The future has to be `Unpin` so that it can be
+/// moved out; use `Box::pin` for futures that aren't.
+pub fn timeout_reclaim<F>(dur: Duration, future: F) -> TimeoutReclaim<F>
+where
+    F: Future + Unpin,
+{
+    TimeoutReclaim {
+        future: Some(future),
+        timeout: Delay::new(dur),
+    }
+}
+
+/// Future returned by the `timeout_reclaim` function.
+#[derive(Debug)]
+pub struct TimeoutReclaim<F> {
+    future: Option<F>,
+    timeout: Delay,
+}
+
+impl<F> Future for TimeoutReclaim<F>
+where
+    F: Future + Unpin,
+{
+    type Output = Result<F::Output, (Elapsed, F)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self
+            .future
+            .as_mut()
+            .expect("TimeoutReclaim polled after completion");
+        if let Poll::Ready(output) = future.poll_unpin(cx) {
+            self.future = None;
+            return Poll::Ready(Ok(output));
+        }
+
+        if self.timeout.poll_unpin(cx).is_ready() {
+            let future = self.future.take().unwrap();
+            Poll::Ready(Err((Elapsed(()), future)))
         } else {
             Poll::Pending
         }
     }
 }
+
+/// How often a `WallTimeout` checks the wall clock, so that it notices the
+/// clock jumping forward.
+const WALL_CLOCK_CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// Creates a new future which waits for `future` until the wall clock reaches
+/// `when`.
+///
+/// Contrary to `timeout`, which measures time with the monotonic clock, this
+/// honors changes of the system clock, e.g. for deadlines which are absolute
+/// timestamps coming from another system: the remaining time is recomputed
+/// from `SystemTime::now` at least every second, so the timeout elapses early
+/// if the clock jumps forward, and later if it steps backward.
+pub fn wall_timeout_at<F>(when: SystemTime, future: F) -> WallTimeout<F>
+where
+    F: TryFuture,
+    F::Error: From<io::Error>,
+{
+    WallTimeout::with_clock(when, future, SystemTime::now)
+}
+
+/// Future returned by the `wall_timeout_at` function.
+#[derive(Debug)]
+pub struct WallTimeout<F>
+where
+    F: TryFuture,
+    F::Error: From<io::Error>,
+{
+    future: F,
+    when: SystemTime,
+    /// Source of the wall clock, which tests replace.
+    now: fn() -> SystemTime,
+    timeout: Delay,
+}
+
+impl<F> WallTimeout<F>
+where
+    F: TryFuture,
+    F::Error: From<io::Error>,
+{
+    #![allow(deprecated)]
+    unsafe_pinned!(future: F);
+    unsafe_unpinned!(timeout: Delay);
+
+    fn with_clock(when: SystemTime, future: F, now: fn() -> SystemTime) -> WallTimeout<F> {
+        let remaining = wall_remaining(when, now);
+        WallTimeout {
+            future,
+            when,
+            now,
+            timeout: Delay::new(cmp::min(remaining, WALL_CLOCK_CHECK_PERIOD)),
+        }
+    }
+}
+
+/// Returns how long the wall clock `now` has to go until `when`.
+fn wall_remaining(when: SystemTime, now: fn() -> SystemTime) -> Duration {
+    when.duration_since(now()).unwrap_or(Duration::ZERO)
+}
+
+impl<F> Future for WallTimeout<F>
+where
+    F: TryFuture,
+    F::Error: From<io::Error>,
+{
+    type Output = Result<F::Ok, F::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.as_mut().future().try_poll(cx) {
+            Poll::Pending => {}
+            other => return other,
+        }
+
+        let (when, now) = (self.when, self.now);
+        let timeout = self.timeout();
+        loop {
+            match timeout.poll_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(())) => {}
+                // The timer is gone, which is reported as is.
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
+            }
+            let remaining = wall_remaining(when, now);
+            if remaining == Duration::ZERO {
+                let err = io::Error::from(Elapsed(()));
+                return Poll::Ready(Err(err.into()));
+            }
+            timeout.reset(cmp::min(remaining, WALL_CLOCK_CHECK_PERIOD));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::channel::oneshot;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use futures::prelude::*;
+
+    use super::{
+        timeout, timeout_at, timeout_reclaim, ScheduledStreamExt, TimeoutSink, WallTimeout,
+    };
+    use crate::{Instant, Interval, SystemTime, Timer, UNIX_EPOCH};
+
+    thread_local! {
+        static WALL_NOW: Cell<SystemTime> = const { Cell::new(UNIX_EPOCH) };
+    }
+
+    fn wall_now() -> SystemTime {
+        WALL_NOW.with(Cell::get)
+    }
+
+    fn set_wall_now(secs: u64) {
+        WALL_NOW.with(|now| now.set(UNIX_EPOCH + Duration::from_secs(secs)));
+    }
+
+    #[wasm_bindgen_test]
+    async fn timeout_reclaim_returns_future() {
+        let (tx, rx) = oneshot::channel::<u32>();
+        let (_, rx) = timeout_reclaim(Duration::from_millis(10), rx)
+            .await
+            .unwrap_err();
+        tx.send(5).unwrap();
+        assert_eq!(rx.await, Ok(5));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn timeout_function() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let is_timed_out = |poll: Poll<io::Result<()>>| match poll {
+            Poll::Ready(Err(err)) => err.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        };
+
+        let mut ok = timeout(Duration::from_secs(10), future::ok::<_, io::Error>(5));
+        assert_eq!(ok.poll_unpin(&mut cx).map(Result::unwrap), Poll::Ready(5));
+        drop(ok);
+        let mut long = timeout(Duration::from_millis(20), future::pending());
+        let mut short = timeout(Duration::from_millis(10), future::pending());
+        assert!(long.poll_unpin(&mut cx).is_pending());
+        assert!(short.poll_unpin(&mut cx).is_pending());
+
+        // Step the timer through both deadlines, rather than waiting.
+        let now = timer.next_deadline().unwrap();
+        assert!(timer.poll_at(now, &mut cx).is_pending());
+        assert!(is_timed_out(short.poll_unpin(&mut cx)));
+        assert!(long.poll_unpin(&mut cx).is_pending());
+        let now = timer.next_deadline().unwrap();
+        assert_eq!(timer.poll_at(now, &mut cx), Poll::Ready(()));
+        assert!(is_timed_out(long.poll_unpin(&mut cx)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn timeout_deadline() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let at = Instant::now() + Duration::from_secs(10);
+        let timeout = timeout_at(at, future::pending::<io::Result<()>>());
+        assert_eq!(timeout.deadline(), at);
+        let timeout = super::timeout(Duration::from_secs(10), future::ok::<(), io::Error>(()));
+        assert!(timeout.deadline() >= at);
+    }
+
+    #[wasm_bindgen_test]
+    async fn schedule_times() {
+        let start = Instant::now() + Duration::from_millis(10);
+        let mut ticks = Interval::new_at(start, Duration::from_millis(10))
+            .take(3)
+            .ticks()
+            .with_schedule_times();
+        let mut scheduled = start;
+        while let Some(tick) = ticks.next().await {
+            assert_eq!(tick.value, scheduled);
+            assert_eq!(tick.scheduled, scheduled);
+            assert!(tick.actual >= scheduled);
+            assert_eq!(tick.jitter(), tick.actual - scheduled);
+            scheduled += Duration::from_millis(10);
+        }
+        assert_eq!(scheduled, start + Duration::from_millis(30));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn wall_timeout_follows_clock_jumps() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let when = UNIX_EPOCH + Duration::from_secs(1000);
+        let pending = || future::pending::<io::Result<()>>();
+        // Fires every wall clock check scheduled so far.
+        let check = |timer: &mut Timer| {
+            timer.fire_expired(Instant::now() + Duration::from_secs(2));
+        };
+
+        // The clock jumping forward past the deadline elapses it early.
+        set_wall_now(900);
+        let mut forward = WallTimeout::with_clock(when, pending(), wall_now);
+        assert!(forward.poll_unpin(&mut cx).is_pending());
+        set_wall_now(1001);
+        check(&mut timer);
+        let err = match forward.poll_unpin(&mut cx) {
+            Poll::Ready(Err(err)) => err,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // The clock stepping backward extends it.
+        set_wall_now(999);
+        let mut backward = WallTimeout::with_clock(when, pending(), wall_now);
+        assert!(backward.poll_unpin(&mut cx).is_pending());
+        set_wall_now(500);
+        check(&mut timer);
+        assert!(backward.poll_unpin(&mut cx).is_pending());
+        check(&mut timer);
+        assert!(backward.poll_unpin(&mut cx).is_pending());
+        set_wall_now(1000);
+        check(&mut timer);
+        assert!(matches!(backward.poll_unpin(&mut cx), Poll::Ready(Err(_))));
+    }
+
+    #[wasm_bindgen_test]
+    async fn wall_timeout_at_resolves_future() {
+        let when = SystemTime::now() + Duration::from_secs(10);
+        let ok = super::wall_timeout_at(when, future::ok::<_, io::Error>(5));
+        assert_eq!(ok.await.unwrap(), 5);
+    }
+
+    /// Sink which is ready, and flushed, only when told to.
+    struct MockSink {
+        ready: Rc<Cell<bool>>,
+        flushed: Rc<Cell<bool>>,
+    }
+
+    impl Sink<u32> for MockSink {
+        type Error = io::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            match self.ready.get() {
+                true => Poll::Ready(Ok(())),
+                false => Poll::Pending,
+            }
+        }
+
+        fn start_send(self: Pin<&mut Self>, _: u32) -> io::Result<()> {
+            self.flushed.set(false);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            match self.flushed.get() {
+                true => Poll::Ready(Ok(())),
+                false => Poll::Pending,
+            }
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn timeout_sink() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let ready = Rc::new(Cell::new(false));
+        let flushed = Rc::new(Cell::new(true));
+        let mut sink = TimeoutSink::new(MockSink {
+            ready: ready.clone(),
+            flushed: flushed.clone(),
+        });
+        sink.set_send_timeout(Some(Duration::from_secs(10)));
+        sink.set_flush_timeout(Some(Duration::from_secs(5)));
+        let later = |secs| Instant::now() + Duration::from_secs(secs);
+
+        // Progress within the timeout.
+        assert!(sink.poll_ready_unpin(&mut cx).is_pending());
+        timer.fire_expired(later(9));
+        assert!(sink.poll_ready_unpin(&mut cx).is_pending());
+        ready.set(true);
+        assert!(matches!(
+            sink.poll_ready_unpin(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        sink.start_send_unpin(1).unwrap();
+
+        // Flushing for too long.
+        assert!(sink.poll_flush_unpin(&mut cx).is_pending());
+        timer.fire_expired(later(6));
+        match sink.poll_flush_unpin(&mut cx) {
+            Poll::Ready(Err(err)) => assert_eq!(err.kind(), io::ErrorKind::TimedOut),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // The timeout restarts with the next wait, and can be removed.
+        assert!(sink.poll_close_unpin(&mut cx).is_pending());
+        timer.fire_expired(later(4));
+        assert!(sink.poll_close_unpin(&mut cx).is_pending());
+        sink.set_flush_timeout(None);
+        flushed.set(true);
+        assert!(matches!(
+            sink.poll_close_unpin(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        flushed.set(false);
+        assert!(sink.poll_flush_unpin(&mut cx).is_pending());
+        timer.fire_expired(later(3600));
+        assert!(sink.poll_flush_unpin(&mut cx).is_pending());
+    }
+}
@@ -55,15 +55,21 @@ impl Drop for HelperThread {
 }
 
 fn run(timer: Timer, done: Arc<AtomicBool>) {
-    let mut waker = current_thread_waker();
-    let mut cx = Context::from_waker(&mut waker);
+    let waker = current_thread_waker();
+    let mut cx = Context::from_waker(&waker);
 
     pin_mut!(timer);
     while !done.load(Ordering::SeqCst) {
-        drop(timer.as_mut().poll(&mut cx));
-
-        timer.advance();
-        match timer.next_event() {
+        let _ = timer.as_mut().poll(&mut cx);
+
+        let _expired = timer.fire(Instant::now());
+        let next_event = timer.next_event();
+        trace_event!(
+            "timer woke up",
+            expired = _expired,
+            next_deadline = next_event,
+        );
+        match next_event {
             // Ok, block for the specified time
             Some(when) => {
                 let now = Instant::now();
@@ -84,7 +90,7 @@ static VTABLE: RawWakerVTable = RawWakerVTable::new(raw_clone, raw_wake, raw_wak
 
 fn raw_clone(ptr: *const ()) -> RawWaker {
     let me = ManuallyDrop::new(unsafe { Arc::from_raw(ptr as *const Thread) });
-    mem::forget(me.clone());
+    mem::forget(Arc::clone(&me));
     RawWaker::new(ptr, &VTABLE)
 }
 
@@ -1,15 +1,32 @@
 use futures::task::{self, ArcWake};
 use parking_lot::Mutex;
+use std::cell::{Cell, RefCell};
 use std::convert::TryFrom;
 use std::future::Future;
+use std::mem::ManuallyDrop;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::{Arc, Weak};
 use std::task::Context;
 use std::time::Duration;
-use wasm_bindgen::{closure::Closure, JsCast};
-use web_sys::WorkerGlobalScope;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{MessageChannel, WorkerGlobalScope};
 
-use crate::{Instant, Timer, TimerHandle};
+use crate::timer::visibility;
+use crate::{Instant, SchedulerHint, Timer, TimerHandle};
+
+/// Browsers clamp nested `setTimeout` calls to at least this duration.
+const SET_TIMEOUT_CLAMP: Duration = Duration::from_millis(4);
+
+thread_local! {
+    static SCHEDULER: RefCell<Option<Scheduler>> = const { RefCell::new(None) };
+
+    /// Number of wake-ups that are currently pending.
+    static ARMED_TIMEOUTS: Cell<usize> = const { Cell::new(0) };
+
+    /// Number of times the timer hit its limit of fires per wake-up and yielded to the browser.
+    static CONTINUATIONS: Cell<usize> = const { Cell::new(0) };
+}
 
 /// Starts a background task, creates a `Timer`, and returns a handle to it.
 ///
@@ -19,75 +36,465 @@ use crate::{Instant, Timer, TimerHandle};
 pub(crate) fn run() -> TimerHandle {
     let timer = Timer::new();
     let handle = timer.handle();
-    schedule_callback(Arc::new(Mutex::new(timer)), Duration::new(0, 0));
+    let timer = Arc::new(Mutex::new(timer));
+
+    let callback = {
+        let timer = Arc::downgrade(&timer);
+        Closure::wrap(Box::new(move || {
+            // Wake-ups which were already queued when the timer was shut down are no-ops.
+            let timer = match timer.upgrade() {
+                Some(timer) => timer,
+                None => return,
+            };
+            SCHEDULER.with(|scheduler| {
+                if let Some(scheduler) = scheduler.borrow_mut().as_mut() {
+                    scheduler.fired();
+                }
+            });
+            process(&timer);
+        }) as Box<dyn FnMut()>)
+    };
+    let waker = task::waker(Arc::new(Waker {
+        timer: Arc::downgrade(&timer),
+    }));
+    let channel = MessageChannel::new().expect("failed to create a MessageChannel");
+    channel
+        .port1()
+        .set_onmessage(Some(callback.as_ref().unchecked_ref()));
+    SCHEDULER.with(|scheduler| {
+        *scheduler.borrow_mut() = Some(Scheduler {
+            _timer: timer.clone(),
+            callback: ManuallyDrop::new(callback),
+            channel,
+            waker,
+            pending: None,
+        })
+    });
+
+    process(&timer);
     handle
 }
 
-/// Calls `Window::setTimeout` with the given `Duration`. The callback wakes up the timer and
-/// processes everything.
-fn schedule_callback(timer: Arc<Mutex<Timer>>, when: Duration) {
-    let callback = Closure::once_into_js(move || {
-        let mut timer_lock = timer.lock();
+/// Shuts down the timer started by `run`, if any.
+///
+/// The pending wake-up is cancelled, and the `Timer` is dropped, which makes all of its delays
+/// fail.
+pub(crate) fn shutdown() {
+    // Dropped outside of the borrow, as dropping the `Timer` wakes up tasks.
+    let scheduler = SCHEDULER.with(|scheduler| scheduler.borrow_mut().take());
+    drop(scheduler);
+}
 
-        // We start by polling the timer. If any new `Delay` is created, the waker will be used
-        // to wake up this task pre-emptively. As such, we pass a `Waker` that calls
-        // `schedule_callback` with a delay of `0`.
-        let waker = task::waker(Arc::new(Waker {
-            timer: timer.clone(),
-        }));
-        let _ = Future::poll(Pin::new(&mut *timer_lock), &mut Context::from_waker(&waker));
+/// State of the JavaScript side of the timer. There is at most one pending wake-up at any given
+/// time, armed for the earliest timer in the heap.
+struct Scheduler {
+    /// The timer. Callbacks and wakers only hold weak references to it, so that it is dropped
+    /// along with the scheduler.
+    _timer: Arc<Mutex<Timer>>,
 
-        // Notify the timers that are ready.
-        let now = Instant::now();
-        timer_lock.advance_to(now);
+    /// Callback invoked on wake-ups, which processes the timer.
+    callback: ManuallyDrop<Closure<dyn FnMut()>>,
+
+    /// Channel whose first port calls `callback` when a message is posted on the second one.
+    channel: MessageChannel,
+
+    /// Waker registered with the `Timer`. It processes the timer whenever a `Delay` is created,
+    /// reset or dropped.
+    waker: std::task::Waker,
 
-        // Each call to `schedule_callback` calls `schedule_callback` again, but also leaves
-        // the possibility for `schedule_callback` to be called in parallel. Since we don't
-        // want too many useless callbacks, we...
-        // TODO: ugh, that's a hack
-        if Arc::strong_count(&timer) > 20 {
+    /// The pending wake-up, and the instant it was armed for.
+    pending: Option<(Wakeup, Instant)>,
+}
+
+/// The ways the timer can be woken up by JavaScript.
+enum Wakeup {
+    /// `setTimeout` with the given handle.
+    Timeout(i32),
+    /// `requestAnimationFrame` with the given handle.
+    AnimationFrame(i32),
+    /// A message posted on the `MessageChannel`, which is delivered on the next turn of the
+    /// event loop without being clamped like nested `setTimeout`s.
+    Message,
+    /// `queueMicrotask`, for timers that are already due.
+    Microtask,
+}
+
+impl Scheduler {
+    /// Makes sure the timer gets processed again no later than `at`.
+    ///
+    /// If the pending wake-up already happens at or before `at`, this is a no-op. A wake-up that
+    /// turns out to happen too early simply processes the timer and re-arms itself.
+    fn arm(&mut self, at: Instant, hint: SchedulerHint, now: Instant) {
+        if let Some((ref wakeup, armed_at)) = self.pending {
+            if armed_at <= at {
+                return;
+            }
+            if !wakeup.cancel() {
+                // Those can't be cancelled, but happen as soon as possible anyway.
+                return;
+            }
+        }
+
+        let wakeup = if at <= now {
+            queue_microtask(self.callback.as_ref().unchecked_ref());
+            Wakeup::Microtask
+        } else {
+            let dur = at - now;
+            let hint = match hint {
+                SchedulerHint::AnimationFrame => match animation_frame_window() {
+                    Some(window) => {
+                        let handle = window
+                            .request_animation_frame(self.callback.as_ref().unchecked_ref())
+                            .expect("failed to request an animation frame");
+                        ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() + 1));
+                        self.pending = Some((Wakeup::AnimationFrame(handle), at));
+                        return;
+                    }
+                    None => SchedulerHint::Coarse,
+                },
+                hint => hint,
+            };
+            let precise = match hint {
+                // Precise wake-ups would be throttled in hidden pages anyway.
+                SchedulerHint::Auto => dur < SET_TIMEOUT_CLAMP && !visibility::is_hidden(),
+                SchedulerHint::Coarse | SchedulerHint::AnimationFrame => false,
+                SchedulerHint::Precise => true,
+            };
+            if precise {
+                self.channel
+                    .port2()
+                    .post_message(&JsValue::NULL)
+                    .expect("failed to post a message");
+                Wakeup::Message
+            } else {
+                Wakeup::Timeout(set_timeout(&self.callback, dur))
+            }
+        };
+        ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() + 1));
+        self.pending = Some((wakeup, at));
+    }
+
+    /// Makes sure the timer gets processed again on a later turn of the event loop, after the
+    /// browser had a chance to run other tasks and render. Used when the timer hit its limit of
+    /// fires per wake-up.
+    fn arm_continuation(&mut self, now: Instant) {
+        if let Some((ref wakeup, _)) = self.pending {
+            if !wakeup.cancel() {
+                // Processes the timer as soon as possible anyway, which arms a continuation again
+                // if need be.
+                return;
+            }
+        }
+        self.channel
+            .port2()
+            .post_message(&JsValue::NULL)
+            .expect("failed to post a message");
+        ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() + 1));
+        CONTINUATIONS.with(|continuations| continuations.set(continuations.get() + 1));
+        self.pending = Some((Wakeup::Message, now));
+    }
+
+    /// Cancels the pending wake-up, if it can be cancelled.
+    fn disarm(&mut self) {
+        if let Some((ref wakeup, _)) = self.pending {
+            if wakeup.cancel() {
+                self.pending = None;
+            }
+        }
+    }
+
+    /// Called when the pending wake-up happens.
+    fn fired(&mut self) {
+        if self.pending.take().is_some() {
+            ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() - 1));
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        if let Some((wakeup, _)) = self.pending.take() {
+            if !wakeup.cancel() {
+                ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() - 1));
+            }
+        }
+        let port = self.channel.port1();
+        port.set_onmessage(None);
+        port.close();
+
+        // A message or microtask might already be queued, and calling a dropped closure throws.
+        // Hand the closure over to the JavaScript garbage collector instead, which frees it once
+        // nothing refers to it anymore.
+        let callback = unsafe { ManuallyDrop::take(&mut self.callback) };
+        let _ = callback.into_js_value();
+    }
+}
+
+impl Wakeup {
+    /// Cancels this wake-up, and returns false if that isn't possible.
+    fn cancel(&self) -> bool {
+        match *self {
+            Wakeup::Timeout(handle) => clear_timeout(handle),
+            Wakeup::AnimationFrame(handle) => cancel_animation_frame(handle),
+            Wakeup::Message | Wakeup::Microtask => return false,
+        }
+        true
+    }
+}
+
+/// Processes the timer: handles new, reset and dropped `Delay`s, notifies the ones that are
+/// ready, then arms the `setTimeout` for the next event.
+fn process(timer: &Mutex<Timer>) {
+    let now = Instant::now();
+    let mut timer_lock = match timer.try_lock() {
+        Some(lock) => lock,
+        None => {
+            // We're woken up while the timer is already being processed, for example because
+            // a `Delay` was created from within a waker. Process it again as soon as possible.
+            SCHEDULER.with(|scheduler| {
+                if let Some(scheduler) = scheduler.borrow_mut().as_mut() {
+                    scheduler.arm(now, SchedulerHint::Auto, now);
+                }
+            });
             return;
         }
+    };
 
-        // We call `schedule_callback` again for the next event.
-        let sleep_dur = timer_lock
-            .next_event()
-            .map(|next_event| {
-                if next_event > now {
-                    next_event - now
-                } else {
-                    Duration::new(0, 0)
+    // We start by polling the timer. If any new `Delay` is created, the waker will be used to
+    // process the timer again pre-emptively.
+    let waker = SCHEDULER.with(|scheduler| {
+        scheduler
+            .borrow()
+            .as_ref()
+            .map(|scheduler| scheduler.waker.clone())
+            // With shared memory, the timer can be woken up from a thread other than the one
+            // driving it. The wake-up can't be forwarded to that thread, and dropping it could
+            // stall the timer forever, so fail loudly instead.
+            .expect(
+                "the global timer was used from a thread other than the one driving it; with \
+                 wasm atomics, delays must stay on the thread they were created on",
+            )
+    });
+    let _ = Future::poll(Pin::new(&mut *timer_lock), &mut Context::from_waker(&waker));
+
+    // Notify the timers that are ready. If there are more than allowed per wake-up, we yield to
+    // the browser before notifying the next ones.
+    let max_fires = timer_lock.inner.max_fires_per_wakeup.load(SeqCst);
+    let expired = timer_lock.fire(now);
+    let capped = expired == max_fires;
+
+    let next_event = timer_lock.next_event_with_hint();
+    drop(timer_lock);
+    trace_event!(
+        "timer woke up",
+        expired = expired,
+        next_deadline = next_event.map(|(at, _)| at),
+    );
+    SCHEDULER.with(|scheduler| {
+        if let Some(scheduler) = scheduler.borrow_mut().as_mut() {
+            match next_event {
+                Some((next_event, _)) if capped && next_event <= now => {
+                    scheduler.arm_continuation(now)
                 }
-            })
-            .unwrap_or(Duration::from_secs(5));
-        drop(timer_lock);
-        schedule_callback(timer, sleep_dur);
+                Some((next_event, hint)) => scheduler.arm(next_event, hint, now),
+                // Nothing left to wait for, in particular no animation frame.
+                None => scheduler.disarm(),
+            }
+        }
     });
+}
+
+/// Calls `setTimeout` with the given `Duration` on the global object, and returns the handle of
+/// the timeout.
+fn set_timeout(callback: &Closure<dyn FnMut()>, dur: Duration) -> i32 {
+    // Round up, so that the timeout doesn't fire right before the timer it's armed for.
+    let millis = dur.as_nanos().div_ceil(1_000_000);
+    let millis = i32::try_from(millis).unwrap_or(i32::MAX);
     if let Some(window) = web_sys::window() {
-        let _ = window
+        window
             .set_timeout_with_callback_and_timeout_and_arguments_0(
-                callback.unchecked_ref(),
-                i32::try_from(when.as_millis()).unwrap_or(0),
+                callback.as_ref().unchecked_ref(),
+                millis,
             )
-            .unwrap();
+            .unwrap()
     } else {
-        let _ = js_sys::global()
+        js_sys::global()
             .dyn_into::<WorkerGlobalScope>()
             .expect("no supported global object available")
             .set_timeout_with_callback_and_timeout_and_arguments_0(
-                callback.unchecked_ref(),
-                i32::try_from(when.as_millis()).unwrap_or(0),
+                callback.as_ref().unchecked_ref(),
+                millis,
             )
-            .unwrap();
+            .unwrap()
+    }
+}
+
+/// Calls `clearTimeout` with the given handle on the global object.
+fn clear_timeout(handle: i32) {
+    if let Some(window) = web_sys::window() {
+        window.clear_timeout_with_handle(handle);
+    } else {
+        js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .expect("no supported global object available")
+            .clear_timeout_with_handle(handle);
+    }
+    ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() - 1));
+}
+
+/// Returns the window to request animation frames from, or `None` if there is
+/// no window or the page is hidden and wouldn't get any frame.
+fn animation_frame_window() -> Option<web_sys::Window> {
+    let window = web_sys::window()?;
+    match window.document() {
+        Some(document) if document.hidden() => None,
+        _ => Some(window),
+    }
+}
+
+/// Calls `cancelAnimationFrame` with the given handle on the window.
+fn cancel_animation_frame(handle: i32) {
+    if let Some(window) = web_sys::window() {
+        let _ = window.cancel_animation_frame(handle);
+    }
+    ARMED_TIMEOUTS.with(|armed| armed.set(armed.get() - 1));
+}
+
+/// Calls `queueMicrotask` with the given callback on the global object.
+fn queue_microtask(callback: &js_sys::Function) {
+    if let Some(window) = web_sys::window() {
+        window.queue_microtask(callback);
+    } else {
+        js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .expect("no supported global object available")
+            .queue_microtask(callback);
     }
 }
 
 struct Waker {
-    timer: Arc<Mutex<Timer>>,
+    timer: Weak<Mutex<Timer>>,
 }
 
 impl ArcWake for Waker {
     fn wake_by_ref(arc_self: &Arc<Self>) {
-        schedule_callback(arc_self.timer.clone(), Duration::new(0, 0));
+        if let Some(timer) = arc_self.timer.upgrade() {
+            process(&timer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    use futures::prelude::*;
+
+    use js_sys::Promise;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_futures::JsFuture;
+
+    use super::{Wakeup, ARMED_TIMEOUTS, CONTINUATIONS, SCHEDULER};
+    use crate::{
+        Delay, GlobalTimerHandle, Instant, Interval, SchedulerHint, TimerDropped, TimerHandle,
+    };
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn armed_timeouts() -> usize {
+        ARMED_TIMEOUTS.with(|armed| armed.get())
+    }
+
+    fn animation_frame_pending() -> bool {
+        SCHEDULER.with(|scheduler| {
+            matches!(
+                scheduler.borrow().as_ref().and_then(|s| s.pending.as_ref()),
+                Some((Wakeup::AnimationFrame(_), _))
+            )
+        })
+    }
+
+    #[wasm_bindgen_test]
+    async fn single_timeout_for_many_delays() {
+        let now = Instant::now();
+        let mut delays = Vec::new();
+        for i in 0..50_000u64 {
+            let at = now + Duration::from_millis(100 + (i * 7_919) % 5_000);
+            delays.push(Delay::new_at(at));
+            assert!(armed_timeouts() <= 1);
+        }
+
+        let mut i = 0;
+        delays.retain(|_| {
+            i += 1;
+            i % 2 == 0
+        });
+        assert!(armed_timeouts() <= 1);
+
+        Delay::new(Duration::from_millis(150)).await.unwrap();
+        assert!(armed_timeouts() <= 1);
+
+        drop(delays);
+        assert!(armed_timeouts() <= 1);
+    }
+
+    #[wasm_bindgen_test]
+    async fn short_interval_beats_set_timeout_clamp() {
+        let start = Instant::now();
+        let mut interval = Interval::new(Duration::from_millis(1));
+        for _ in 0..200 {
+            interval.next().await;
+        }
+        // With `setTimeout` alone, each tick would take at least 4 ms.
+        assert!(start.elapsed() < Duration::from_millis(600));
+    }
+
+    #[wasm_bindgen_test]
+    async fn animation_frame_delay() {
+        let mut delay = Delay::new(Duration::from_secs(10));
+        delay.set_scheduler_hint(SchedulerHint::AnimationFrame);
+        delay.reset(Duration::from_millis(50));
+        assert!(animation_frame_pending());
+        (&mut delay).await.unwrap();
+        assert!(!animation_frame_pending());
+    }
+
+    #[wasm_bindgen_test]
+    async fn shutdown_fails_pending_delays() {
+        let mut delay = Delay::new(Duration::from_millis(10));
+        let _ = futures::poll!(&mut delay);
+        GlobalTimerHandle::shutdown();
+        assert_eq!(armed_timeouts(), 0);
+
+        // Let already queued callbacks run.
+        for _ in 0..10 {
+            JsFuture::from(Promise::resolve(&JsValue::NULL))
+                .await
+                .unwrap();
+        }
+        // This starts a new global timer.
+        Delay::new(Duration::from_millis(30)).await.unwrap();
+
+        let err = (&mut delay).await.unwrap_err();
+        assert!(err.get_ref().is_some_and(|err| err.is::<TimerDropped>()));
+    }
+
+    #[wasm_bindgen_test]
+    async fn bounded_fires_per_wakeup() {
+        let handle = TimerHandle::default();
+        handle.set_max_fires_per_wakeup(50);
+        let continuations = CONTINUATIONS.with(|continuations| continuations.get());
+
+        let at = Instant::now() + Duration::from_millis(20);
+        let delays: Vec<_> = (0..1000).map(|_| Delay::new_at(at)).collect();
+        for result in future::join_all(delays).await {
+            result.unwrap();
+        }
+        let continuations = CONTINUATIONS.with(|continuations| continuations.get()) - continuations;
+        assert!(continuations >= 1000 / 50 - 1);
+
+        handle.set_max_fires_per_wakeup(usize::MAX);
     }
 }
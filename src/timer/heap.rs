@@ -8,100 +8,499 @@
 //! Note that this heap is not at all optimized right now, it should hopefully
 //! just work.
 
-use std::mem;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::mem;
+use core::slice;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-pub struct Heap<T> {
+/// Returns the generation identifying a new `Heap`.
+#[cfg(target_has_atomic = "ptr")]
+fn next_generation() -> usize {
+    static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Returns the generation identifying a new `Heap`.
+///
+/// Without atomics, all heaps share the same generation, so using a slot with
+/// another heap than its own isn't detected.
+#[cfg(not(target_has_atomic = "ptr"))]
+fn next_generation() -> usize {
+    0
+}
+
+/// A binary min-heap of key-value pairs, ordered by key, supporting removal of
+/// arbitrary elements.
+///
+/// Only the keys are compared: values don't need to implement `Ord`, and
+/// elements with equal keys are popped in an unspecified order.
+pub struct Heap<K, V> {
     // Binary heap of items, plus the slab index indicating what position in the
     // list they're in.
-    items: Vec<(T, usize)>,
+    items: Vec<(K, V, usize)>,
 
     // A map from a slab index (assigned to an item above) to the actual index
     // in the array the item appears at.
     index: Vec<SlabSlot<usize>>,
     next_index: usize,
+
+    // Unique identity of this heap, embedded in the slots it hands out.
+    generation: usize,
+
+    // Whether `items` may violate the heap property because of an `iter_mut`,
+    // in which case it is restored by the next mutating method.
+    dirty: bool,
 }
 
-enum SlabSlot<T> {
-    Empty { next: usize },
-    Full { value: T },
+/// An entry of the slab mapping slots to positions in a `Heap`, as exposed by
+/// `Heap::into_raw_parts`.
+///
+/// Entries also carry a version, bumped whenever their element is removed, so
+/// that stale slots don't alias the elements which reuse them.
+pub enum SlabSlot<T> {
+    /// A vacant entry, linked to the next vacant one.
+    Empty { next: usize, version: u64 },
+    /// An entry in use, holding the position of its element.
+    Full { value: T, version: u64 },
 }
 
+/// The parts of a `Heap`, as returned by `Heap::into_raw_parts`: its elements
+/// along with their slab indices, its slab, and the index of its first vacant
+/// slab entry.
+pub type RawParts<K, V> = (Vec<(K, V, usize)>, Vec<SlabSlot<usize>>, usize);
+
+/// A token referring to an element pushed onto a `Heap`.
+///
+/// A slot can only be used with the heap it was obtained from. Once its
+/// element is removed, the slot is stale: it doesn't refer to the elements
+/// pushed afterwards.
 pub struct Slot {
     idx: usize,
+    generation: usize,
+    version: u64,
 }
 
-impl<T: Ord> Heap<T> {
-    pub fn new() -> Heap<T> {
+impl<K, V> Heap<K, V> {
+    /// Creates a new, empty heap.
+    pub fn new() -> Heap<K, V> {
         Heap {
             items: Vec::new(),
             index: Vec::new(),
             next_index: 0,
+            generation: next_generation(),
+            dirty: false,
+        }
+    }
+
+    /// Returns the number of elements in this heap.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if this heap contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns an estimate, in bytes, of the memory allocated by this heap.
+    ///
+    /// Only the capacity of its buffers is accounted for, not the overhead of
+    /// the allocator nor memory the elements themselves point to.
+    pub fn memory_usage(&self) -> usize {
+        self.items.capacity() * mem::size_of::<(K, V, usize)>()
+            + self.index.capacity() * mem::size_of::<SlabSlot<usize>>()
+    }
+
+    /// Returns the ratio of the slab entries which are vacant, between 0 and
+    /// 1. Vacant entries are reused by later pushes, but never freed.
+    pub fn slab_fragmentation(&self) -> f64 {
+        (self.index.len() - self.items.len()) as f64 / self.index.len().max(1) as f64
+    }
+
+    /// Returns an iterator over the elements of this heap along with their
+    /// slots, in no particular order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            items: self.items.iter(),
+            index: &self.index,
+            generation: self.generation,
         }
     }
 
+    /// Returns an iterator over mutable references to the elements of this
+    /// heap, in no particular order.
+    ///
+    /// The keys may be modified in any way, e.g. to shift all deadlines by
+    /// the same offset. The heap property is restored in O(n) by the next
+    /// call to a method taking `&mut self`; until then `position_of` and
+    /// `depth_of` return unspecified values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.dirty = true;
+        IterMut {
+            items: self.items.iter_mut(),
+        }
+    }
+
+    /// Returns the position in the underlying array of the element that
+    /// `slot` refers to.
+    ///
+    /// Position 0 is the root of the heap, i.e. the minimum element.
+    pub fn position_of(&self, slot: &Slot) -> usize {
+        self.assert_owns(slot);
+        match self.lookup(slot) {
+            Some(idx) => idx,
+            None => panic!("slot refers to an element which was removed"),
+        }
+    }
+
+    /// Returns references to the key and value of the element that `slot`
+    /// refers to.
+    pub fn get(&self, slot: &Slot) -> (&K, &V) {
+        let (key, value, _) = &self.items[self.position_of(slot)];
+        (key, value)
+    }
+
+    /// Returns references to the key and value of the element that `slot`
+    /// refers to, or `None` if the element was removed or `slot` was obtained
+    /// from another heap.
+    pub fn try_get(&self, slot: &Slot) -> Option<(&K, &V)> {
+        self.lookup(slot).map(|idx| {
+            let (key, value, _) = &self.items[idx];
+            (key, value)
+        })
+    }
+
+    /// Returns the position in `items` of the element that `slot` refers to,
+    /// if `slot` isn't stale and belongs to this heap.
+    fn lookup(&self, slot: &Slot) -> Option<usize> {
+        if slot.generation != self.generation {
+            return None;
+        }
+        match *self.index.get(slot.idx)? {
+            SlabSlot::Full { value, version } if version == slot.version => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the depth in the tree of the element that `slot` refers to.
+    ///
+    /// The root of the heap is at depth 0.
+    pub fn depth_of(&self, slot: &Slot) -> usize {
+        (self.position_of(slot) + 1).ilog2() as usize
+    }
+
+    fn assert_owns(&self, slot: &Slot) {
+        assert_eq!(
+            slot.generation, self.generation,
+            "slot used with a heap it wasn't obtained from"
+        );
+    }
+}
+
+impl<K: Ord, V> Heap<K, V> {
     /// Pushes an element onto this heap, returning a slot token indicating
     /// where it was pushed on to.
     ///
     /// The slot can later get passed to `remove` to remove the element from the
     /// heap, but only if the element was previously not removed from the heap.
-    pub fn push(&mut self, t: T) -> Slot {
+    pub fn push(&mut self, key: K, value: V) -> Slot {
+        self.restore();
         self.assert_consistent();
         let len = self.items.len();
-        let slot = SlabSlot::Full { value: len };
-        let slot_idx = if self.next_index == self.index.len() {
+        let (slot_idx, version) = if self.next_index == self.index.len() {
             self.next_index += 1;
-            self.index.push(slot);
-            self.index.len() - 1
+            self.index.push(SlabSlot::Full {
+                value: len,
+                version: 0,
+            });
+            (self.index.len() - 1, 0)
         } else {
-            match mem::replace(&mut self.index[self.next_index], slot) {
-                SlabSlot::Empty { next } => mem::replace(&mut self.next_index, next),
+            let slot_idx = self.next_index;
+            match self.index[slot_idx] {
+                SlabSlot::Empty { next, version } => {
+                    self.next_index = next;
+                    self.index[slot_idx] = SlabSlot::Full {
+                        value: len,
+                        version,
+                    };
+                    (slot_idx, version)
+                }
                 SlabSlot::Full { .. } => panic!(),
             }
         };
-        self.items.push((t, slot_idx));
+        self.items.push((key, value, slot_idx));
         self.percolate_up(len);
         self.assert_consistent();
-        Slot { idx: slot_idx }
+        Slot {
+            idx: slot_idx,
+            generation: self.generation,
+            version,
+        }
     }
 
-    pub fn peek(&self) -> Option<&T> {
-        self.assert_consistent();
-        self.items.first().map(|i| &i.0)
+    /// Decomposes this heap into its elements along with their slab indices,
+    /// its slab, and the index of its first vacant slab entry.
+    ///
+    /// # Safety
+    ///
+    /// The parts are meant to be given back to `from_raw_parts` unchanged,
+    /// e.g. after moving them to another worker, or changed in a way which
+    /// keeps the invariants of the heap.
+    pub unsafe fn into_raw_parts(mut self) -> RawParts<K, V> {
+        self.restore();
+        (self.items, self.index, self.next_index)
+    }
+
+    /// Rebuilds a heap from the parts returned by `into_raw_parts`.
+    ///
+    /// The slots obtained from the original heap don't refer to the elements
+    /// of the new one.
+    ///
+    /// # Safety
+    ///
+    /// The parts must satisfy the invariants of the heap: `items` has the heap
+    /// property, the slab holds the position of every element in `items`, and
+    /// its vacant entries are linked from `next_index`. This is only checked
+    /// in debug builds, and a violation is otherwise found out by a panic, or
+    /// wrong results, of later calls.
+    pub unsafe fn from_raw_parts(
+        items: Vec<(K, V, usize)>,
+        index: Vec<SlabSlot<usize>>,
+        next_index: usize,
+    ) -> Heap<K, V> {
+        let heap = Heap {
+            items,
+            index,
+            next_index,
+            generation: next_generation(),
+            dirty: false,
+        };
+        heap.debug_assert_valid();
+        heap
+    }
+
+    /// Returns the key and value of the minimum element of this heap, if any.
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        let item = if self.dirty {
+            self.items.iter().min_by(|a, b| a.0.cmp(&b.0))
+        } else {
+            self.assert_consistent();
+            self.items.first()
+        };
+        item.map(|(key, value, _)| (key, value))
     }
 
-    pub fn pop(&mut self) -> Option<T> {
+    /// Removes and returns the minimum element of this heap, if any.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        self.pop_with_slot().map(|(key, value, _)| (key, value))
+    }
+
+    /// Same as `pop`, but also returns the slot the element was referred to
+    /// by, e.g. to correlate it with an element pushed back later.
+    ///
+    /// The returned slot is stale. If the element is pushed again, it gets a
+    /// new slot, whose `id` is the same if no other element was pushed in the
+    /// meantime.
+    pub fn pop_with_slot(&mut self) -> Option<(K, V, Slot)> {
+        self.restore();
         self.assert_consistent();
         if self.items.is_empty() {
             return None;
         }
+        let slot_idx = self.items[0].2;
+        let version = match self.index[slot_idx] {
+            SlabSlot::Full { version, .. } => version,
+            SlabSlot::Empty { .. } => panic!(),
+        };
         let slot = Slot {
-            idx: self.items[0].1,
+            idx: slot_idx,
+            generation: self.generation,
+            version,
         };
-        Some(self.remove(slot))
+        let (key, value) = self.remove(Slot { ..slot });
+        Some((key, value, slot))
+    }
+
+    /// Removes and returns the minimum element of this heap, but only if its
+    /// key is less than or equal to `threshold`. Otherwise the heap is left
+    /// untouched and `None` is returned.
+    pub fn remove_min_if_le(&mut self, threshold: &K) -> Option<(K, V)> {
+        self.restore();
+        match self.items.first() {
+            Some((min, _, _)) if min <= threshold => self.pop(),
+            _ => None,
+        }
     }
 
-    pub fn remove(&mut self, slot: Slot) -> T {
+    /// Removes every element whose key is less than or equal to `threshold`,
+    /// and returns them in ascending order of key.
+    ///
+    /// This is the same as calling `remove_min_if_le` until it returns `None`,
+    /// e.g. to collect all the due timers at once.
+    pub fn pop_le(&mut self, threshold: &K) -> Vec<(K, V)> {
+        let mut popped = Vec::new();
+        while let Some(item) = self.remove_min_if_le(threshold) {
+            popped.push(item);
+        }
+        popped
+    }
+
+    /// Replaces the minimum element of this heap with the given one, and
+    /// returns the former minimum along with the slot of the new element. This
+    /// is equivalent to a `pop` followed by a `push`, but restructures the heap
+    /// only once.
+    ///
+    /// The slab entry of the former minimum is reused for the new element, but
+    /// with a new version, so that the slot of the former minimum is stale and
+    /// doesn't refer to the new element. If the heap is empty, the new element
+    /// is pushed onto it and `None` is returned in place of the former minimum.
+    pub fn replace_root(&mut self, key: K, value: V) -> (Option<(K, V)>, Slot) {
+        self.restore();
         self.assert_consistent();
-        let empty = SlabSlot::Empty {
-            next: self.next_index,
-        };
-        let idx = match mem::replace(&mut self.index[slot.idx], empty) {
-            SlabSlot::Full { value } => value,
+        if self.items.is_empty() {
+            return (None, self.push(key, value));
+        }
+        let root = &mut self.items[0];
+        let old = (
+            mem::replace(&mut root.0, key),
+            mem::replace(&mut root.1, value),
+        );
+        let slot_idx = root.2;
+        let version = match self.index[slot_idx] {
+            SlabSlot::Full { version, .. } => version.wrapping_add(1),
             SlabSlot::Empty { .. } => panic!(),
         };
+        self.index[slot_idx] = SlabSlot::Full { value: 0, version };
+        self.percolate_down(0);
+        self.assert_consistent();
+        let slot = Slot {
+            idx: slot_idx,
+            generation: self.generation,
+            version,
+        };
+        (Some(old), slot)
+    }
+
+    /// Removes the element that `slot` refers to from this heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element was already removed from the heap, or if `slot`
+    /// was obtained from another heap.
+    pub fn remove(&mut self, slot: Slot) -> (K, V) {
+        self.assert_owns(&slot);
+        match self.try_remove(&slot) {
+            Some(item) => item,
+            None => panic!("slot refers to an element which was removed"),
+        }
+    }
+
+    /// Removes the element that `slot` refers to from this heap, or returns
+    /// `None` if the element was already removed or `slot` was obtained from
+    /// another heap.
+    pub fn try_remove(&mut self, slot: &Slot) -> Option<(K, V)> {
+        self.restore();
+        self.assert_consistent();
+        let idx = self.lookup(slot)?;
+        self.index[slot.idx] = SlabSlot::Empty {
+            next: self.next_index,
+            version: slot.version.wrapping_add(1),
+        };
         self.next_index = slot.idx;
-        let (item, slot_idx) = self.items.swap_remove(idx);
+        let (key, value, slot_idx) = self.items.swap_remove(idx);
         debug_assert_eq!(slot.idx, slot_idx);
         if idx < self.items.len() {
-            set_index(&mut self.index, self.items[idx].1, idx);
-            if self.items[idx].0 < item {
+            set_index(&mut self.index, self.items[idx].2, idx);
+            if self.items[idx].0 < key {
                 self.percolate_up(idx);
             } else {
                 self.percolate_down(idx);
             }
         }
         self.assert_consistent();
-        item
+        Some((key, value))
+    }
+
+    /// Removes the elements that `slots` refer to, and returns how many of
+    /// them were removed.
+    ///
+    /// Contrary to calling `remove` for each slot, the heap property is only
+    /// restored once, in O(n). Stale slots, and slots obtained from another
+    /// heap, are skipped.
+    pub fn remove_all(&mut self, slots: &[Slot]) -> usize {
+        let mut removed = 0;
+        for slot in slots {
+            if self.lookup(slot).is_none() {
+                continue;
+            }
+            self.index[slot.idx] = SlabSlot::Empty {
+                next: self.next_index,
+                version: slot.version.wrapping_add(1),
+            };
+            self.next_index = slot.idx;
+            removed += 1;
+        }
+        if removed == 0 {
+            return 0;
+        }
+        let index = &self.index;
+        self.items
+            .retain(|&(_, _, slot_idx)| matches!(index[slot_idx], SlabSlot::Full { .. }));
+        for (idx, &(_, _, slot_idx)) in self.items.iter().enumerate() {
+            set_index(&mut self.index, slot_idx, idx);
+        }
+        self.dirty = true;
+        self.restore();
+        self.assert_consistent();
+        removed
+    }
+
+    /// Removes every element for which `f` returns `false`, in O(n).
+    ///
+    /// The slots of the elements which are kept remain valid.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut idx = 0;
+        while idx < self.items.len() {
+            let (key, value, _) = &self.items[idx];
+            if f(key, value) {
+                idx += 1;
+                continue;
+            }
+            let (_, _, slot_idx) = self.items.swap_remove(idx);
+            let version = match self.index[slot_idx] {
+                SlabSlot::Full { version, .. } => version,
+                SlabSlot::Empty { .. } => panic!(),
+            };
+            self.index[slot_idx] = SlabSlot::Empty {
+                next: self.next_index,
+                version: version.wrapping_add(1),
+            };
+            self.next_index = slot_idx;
+        }
+        for (idx, &(_, _, slot_idx)) in self.items.iter().enumerate() {
+            set_index(&mut self.index, slot_idx, idx);
+        }
+        self.dirty = true;
+        self.restore();
+        self.assert_consistent();
+    }
+
+    /// Restores the heap property after a call to `iter_mut`, sifting down
+    /// every parent node from the last one (Floyd's algorithm).
+    fn restore(&mut self) {
+        if !mem::replace(&mut self.dirty, false) {
+            return;
+        }
+        for idx in (0..self.items.len() / 2).rev() {
+            self.percolate_down(idx);
+        }
     }
 
     fn percolate_up(&mut self, mut idx: usize) -> usize {
@@ -112,8 +511,8 @@ impl<T: Ord> Heap<T> {
             }
             let (a, b) = self.items.split_at_mut(idx);
             mem::swap(&mut a[parent], &mut b[0]);
-            set_index(&mut self.index, a[parent].1, parent);
-            set_index(&mut self.index, b[0].1, idx);
+            set_index(&mut self.index, a[parent].2, parent);
+            set_index(&mut self.index, b[0].2, idx);
             idx = parent;
         }
         idx
@@ -153,176 +552,666 @@ impl<T: Ord> Heap<T> {
                 self.items.split_at_mut(right)
             };
             mem::swap(&mut a[idx], &mut b[0]);
-            set_index(&mut self.index, a[idx].1, idx);
-            set_index(&mut self.index, b[0].1, a.len());
+            set_index(&mut self.index, a[idx].2, idx);
+            set_index(&mut self.index, b[0].2, a.len());
             idx = a.len();
         }
         idx
     }
 
+    /// Checks the invariants of this heap, and panics if one of them doesn't
+    /// hold.
+    ///
+    /// Only does something in debug builds, e.g. to verify a scheduler built
+    /// on top of the heap from its tests. See `is_valid`.
+    pub fn debug_assert_valid(&self) {
+        #[cfg(debug_assertions)]
+        if let Some(violation) = self.violation() {
+            panic!("{}", violation);
+        }
+    }
+
+    /// Returns whether the invariants of this heap hold: every element is
+    /// reachable through its slot, and no key is smaller than its parent's.
+    ///
+    /// The heap property isn't checked after a call to `iter_mut`, until it's
+    /// restored by the next call to a method taking `&mut self`.
+    pub fn is_valid(&self) -> bool {
+        self.violation().is_none()
+    }
+
     fn assert_consistent(&self) {
         #[allow(unexpected_cfgs)]
         if !cfg!(assert_timer_heap_consistent) {
             return;
         }
+        if let Some(violation) = self.violation() {
+            panic!("{}", violation);
+        }
+    }
 
-        assert_eq!(
-            self.items.len(),
-            self.index
-                .iter()
-                .filter(|slot| {
-                    match **slot {
-                        SlabSlot::Full { .. } => true,
-                        SlabSlot::Empty { .. } => false,
-                    }
-                })
-                .count()
-        );
+    /// Returns a description of the first invariant of this heap found not to
+    /// hold, if any.
+    fn violation(&self) -> Option<String> {
+        let full = self
+            .index
+            .iter()
+            .filter(|slot| match **slot {
+                SlabSlot::Full { .. } => true,
+                SlabSlot::Empty { .. } => false,
+            })
+            .count();
+        if self.items.len() != full {
+            return Some(format!(
+                "{} items but {} full slab slots",
+                self.items.len(),
+                full
+            ));
+        }
 
-        for (i, &(_, j)) in self.items.iter().enumerate() {
-            let index = match self.index[j] {
-                SlabSlot::Full { value } => value,
-                SlabSlot::Empty { .. } => panic!(),
-            };
-            if index != i {
-                panic!(
-                    "self.index[j] != i : i={} j={} self.index[j]={}",
-                    i, j, index
-                );
+        for (i, &(_, _, j)) in self.items.iter().enumerate() {
+            match self.index.get(j) {
+                Some(&SlabSlot::Full { value, .. }) if value == i => {}
+                Some(&SlabSlot::Full { value, .. }) => {
+                    return Some(format!(
+                        "self.index[j] != i : i={} j={} self.index[j]={}",
+                        i, j, value
+                    ))
+                }
+                _ => return Some(format!("no full slab slot for item: i={} j={}", i, j)),
             }
         }
 
-        for (i, (item, _)) in self.items.iter().enumerate() {
-            if i > 0 {
-                assert!(*item >= self.items[(i - 1) / 2].0, "bad at index: {i}");
+        if self.dirty {
+            return None;
+        }
+        for (i, (key, _, _)) in self.items.iter().enumerate() {
+            if i > 0 && *key < self.items[(i - 1) / 2].0 {
+                return Some(format!("bad at index: {i}"));
             }
             if let Some(left) = self.items.get(2 * i + 1) {
-                assert!(*item <= left.0, "bad left at index: {i}");
+                if *key > left.0 {
+                    return Some(format!("bad left at index: {i}"));
+                }
             }
             if let Some(right) = self.items.get(2 * i + 2) {
-                assert!(*item <= right.0, "bad right at index: {i}");
+                if *key > right.0 {
+                    return Some(format!("bad right at index: {i}"));
+                }
             }
         }
+        None
+    }
+}
+
+/// Iterator over the elements of a `Heap` and their slots, returned by
+/// `Heap::iter`.
+pub struct Iter<'a, K, V> {
+    items: slice::Iter<'a, (K, V, usize)>,
+    index: &'a [SlabSlot<usize>],
+    generation: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V, Slot);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V, Slot)> {
+        let (key, value, idx) = self.items.next()?;
+        let version = match self.index[*idx] {
+            SlabSlot::Full { version, .. } => version,
+            SlabSlot::Empty { .. } => panic!(),
+        };
+        let slot = Slot {
+            idx: *idx,
+            generation: self.generation,
+            version,
+        };
+        Some((key, value, slot))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+/// Iterator over mutable references to the keys and values of a `Heap`,
+/// returned by `Heap::iter_mut`.
+pub struct IterMut<'a, K, V> {
+    items: slice::IterMut<'a, (K, V, usize)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a mut K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a mut K, &'a mut V)> {
+        self.items.next().map(|(key, value, _)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> Default for Heap<K, V> {
+    fn default() -> Heap<K, V> {
+        Heap::new()
+    }
+}
+
+/// A binary max-heap of key-value pairs, ordered by key, supporting removal of
+/// arbitrary elements.
+///
+/// This is a `Heap` whose keys are wrapped in `Reverse`, e.g. to expire the
+/// longest-waiting timer first.
+pub struct MaxHeap<K, V> {
+    heap: Heap<Reverse<K>, V>,
+}
+
+impl<K, V> MaxHeap<K, V> {
+    /// Creates a new, empty heap.
+    pub fn new() -> MaxHeap<K, V> {
+        MaxHeap { heap: Heap::new() }
+    }
+
+    /// Returns the number of elements in this heap.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns true if this heap has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl<K: Ord, V> MaxHeap<K, V> {
+    /// Pushes an element onto this heap, returning a slot token which can
+    /// later get passed to `remove`. See `Heap::push`.
+    pub fn push(&mut self, key: K, value: V) -> Slot {
+        self.heap.push(Reverse(key), value)
+    }
+
+    /// Returns the key and value of the maximum element of this heap, if any.
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        self.heap.peek().map(|(key, value)| (&key.0, value))
+    }
+
+    /// Removes and returns the maximum element of this heap, if any.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        self.heap.pop().map(|(key, value)| (key.0, value))
+    }
+
+    /// Removes the element that `slot` refers to from this heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element was already removed from the heap, or if `slot`
+    /// was obtained from another heap.
+    pub fn remove(&mut self, slot: Slot) -> (K, V) {
+        let (key, value) = self.heap.remove(slot);
+        (key.0, value)
+    }
+
+    /// Removes the element that `slot` refers to from this heap, or returns
+    /// `None` if the element was already removed or `slot` was obtained from
+    /// another heap.
+    pub fn try_remove(&mut self, slot: &Slot) -> Option<(K, V)> {
+        self.heap
+            .try_remove(slot)
+            .map(|(key, value)| (key.0, value))
+    }
+}
+
+impl<K, V> Default for MaxHeap<K, V> {
+    fn default() -> MaxHeap<K, V> {
+        MaxHeap::new()
+    }
+}
+
+impl Slot {
+    /// Returns an identifier of the element this slot refers to, unique among
+    /// the elements currently in its heap. Identifiers of removed elements are
+    /// reused.
+    pub fn id(&self) -> usize {
+        self.idx
+    }
+}
+
+/// Returns a placeholder slot, e.g. for a field which is set once an element
+/// is pushed.
+///
+/// A default `Slot` isn't valid for use with any heap: passing it to `remove`
+/// or `position_of` panics.
+impl Default for Slot {
+    fn default() -> Slot {
+        Slot {
+            idx: usize::MAX,
+            // Generations are handed out from zero, so no heap has this one.
+            generation: usize::MAX,
+            version: 0,
+        }
     }
 }
 
 fn set_index<T>(slab: &mut [SlabSlot<T>], slab_slot: usize, val: T) {
     match slab[slab_slot] {
-        SlabSlot::Full { ref mut value } => *value = val,
+        SlabSlot::Full { ref mut value, .. } => *value = val,
         SlabSlot::Empty { .. } => panic!(),
     }
 }
 
+/// Harness of the fuzz target of the heap, also run on the regression inputs
+/// of the tests.
+#[cfg(any(test, fuzzing))]
+#[doc(hidden)]
+pub mod fuzz {
+    use alloc::vec::Vec;
+
+    use super::{Heap, Slot};
+
+    /// Applies the operations decoded from `data` to a heap, checking it
+    /// against a naive model, and its invariants, after each of them. Each
+    /// element gets a unique value, to check it stays with its key.
+    ///
+    /// Each operation takes a byte, whose value modulo 4 selects it: push a
+    /// key decoded from the next 8 bytes, pop, remove the live element whose
+    /// index is given by the next byte, or update it to a new key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the heap misbehaves.
+    pub fn run(mut data: &[u8]) {
+        let mut heap = Heap::new();
+        let mut live: Vec<(Slot, i64, usize)> = Vec::new();
+        let mut pushed = 0;
+        let mut push = |heap: &mut Heap<i64, usize>, key| {
+            pushed += 1;
+            (heap.push(key, pushed), key, pushed)
+        };
+        while let Some((&op, rest)) = data.split_first() {
+            data = rest;
+            match op % 4 {
+                0 => {
+                    let key = key(&mut data);
+                    live.push(push(&mut heap, key));
+                }
+                1 => match heap.pop_with_slot() {
+                    Some((key, value, slot)) => {
+                        assert_eq!(Some(key), live.iter().map(|&(_, key, _)| key).min());
+                        let pos = live.iter().position(|(s, _, _)| s.id() == slot.id());
+                        let (_, live_key, live_value) = live.swap_remove(pos.unwrap());
+                        assert_eq!((live_key, live_value), (key, value));
+                    }
+                    None => assert!(live.is_empty()),
+                },
+                op => {
+                    let nth = match data.split_first() {
+                        Some((&nth, rest)) if !live.is_empty() => {
+                            data = rest;
+                            nth as usize % live.len()
+                        }
+                        _ => continue,
+                    };
+                    let (slot, key, value) = live.swap_remove(nth);
+                    assert_eq!(heap.remove(slot), (key, value));
+                    if op == 3 {
+                        let key = key.wrapping_add(self::key(&mut data));
+                        live.push(push(&mut heap, key));
+                    }
+                }
+            }
+            assert!(heap.is_valid());
+            assert_eq!(heap.len(), live.len());
+        }
+    }
+
+    /// Decodes a key from the first 8 bytes of `data`, or fewer if there
+    /// aren't enough.
+    fn key(data: &mut &[u8]) -> i64 {
+        let len = data.len().min(8);
+        let mut bytes = [0; 8];
+        bytes[..len].copy_from_slice(&data[..len]);
+        *data = &data[len..];
+        i64::from_le_bytes(bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use alloc::collections::BTreeMap;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
     use wasm_bindgen_test::wasm_bindgen_test;
 
-    use super::Heap;
+    use super::{Heap, MaxHeap, Slot};
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn simple() {
         let mut h = Heap::new();
-        h.push(1);
-        h.push(2);
-        h.push(8);
-        h.push(4);
-        assert_eq!(h.pop(), Some(1));
-        assert_eq!(h.pop(), Some(2));
-        assert_eq!(h.pop(), Some(4));
-        assert_eq!(h.pop(), Some(8));
+        h.push(1, ());
+        h.push(2, ());
+        h.push(8, ());
+        h.push(4, ());
+        assert_eq!(h.pop(), Some((1, ())));
+        assert_eq!(h.pop(), Some((2, ())));
+        assert_eq!(h.pop(), Some((4, ())));
+        assert_eq!(h.pop(), Some((8, ())));
         assert_eq!(h.pop(), None);
         assert_eq!(h.pop(), None);
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn simple2() {
         let mut h = Heap::new();
-        h.push(5);
-        h.push(4);
-        h.push(3);
-        h.push(2);
-        h.push(1);
-        assert_eq!(h.pop(), Some(1));
-        h.push(8);
-        assert_eq!(h.pop(), Some(2));
-        h.push(1);
-        assert_eq!(h.pop(), Some(1));
-        assert_eq!(h.pop(), Some(3));
-        assert_eq!(h.pop(), Some(4));
-        h.push(5);
-        assert_eq!(h.pop(), Some(5));
-        assert_eq!(h.pop(), Some(5));
-        assert_eq!(h.pop(), Some(8));
-    }
-
-    #[wasm_bindgen_test]
+        h.push(5, ());
+        h.push(4, ());
+        h.push(3, ());
+        h.push(2, ());
+        h.push(1, ());
+        assert_eq!(h.pop(), Some((1, ())));
+        h.push(8, ());
+        assert_eq!(h.pop(), Some((2, ())));
+        h.push(1, ());
+        assert_eq!(h.pop(), Some((1, ())));
+        assert_eq!(h.pop(), Some((3, ())));
+        assert_eq!(h.pop(), Some((4, ())));
+        h.push(5, ());
+        assert_eq!(h.pop(), Some((5, ())));
+        assert_eq!(h.pop(), Some((5, ())));
+        assert_eq!(h.pop(), Some((8, ())));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn remove() {
         let mut h = Heap::new();
-        h.push(5);
-        h.push(4);
-        h.push(3);
-        let two = h.push(2);
-        h.push(1);
-        assert_eq!(h.pop(), Some(1));
-        assert_eq!(h.remove(two), 2);
-        h.push(1);
-        assert_eq!(h.pop(), Some(1));
-        assert_eq!(h.pop(), Some(3));
-    }
-
-    fn vec2heap<T: Ord>(v: Vec<T>) -> Heap<T> {
+        h.push(5, ());
+        h.push(4, ());
+        h.push(3, ());
+        let two = h.push(2, ());
+        h.push(1, ());
+        assert_eq!(h.pop(), Some((1, ())));
+        assert_eq!(h.remove(two), (2, ()));
+        h.push(1, ());
+        assert_eq!(h.pop(), Some((1, ())));
+        assert_eq!(h.pop(), Some((3, ())));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn remove_min_if_le() {
+        let mut h = Heap::new();
+        assert_eq!(h.remove_min_if_le(&3), None);
+        h.push(4, ());
+        h.push(2, ());
+        h.push(6, ());
+        assert_eq!(h.remove_min_if_le(&1), None);
+        assert_eq!(h.len(), 3);
+        assert_eq!(h.remove_min_if_le(&2), Some((2, ())));
+        assert_eq!(h.remove_min_if_le(&3), None);
+        assert_eq!(h.peek(), Some((&4, &())));
+        assert_eq!(h.remove_min_if_le(&10), Some((4, ())));
+        assert_eq!(h.remove_min_if_le(&10), Some((6, ())));
+        assert!(h.is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn pop_le() {
+        let mut h = Heap::new();
+        assert!(h.pop_le(&10).is_empty());
+        for (key, value) in [
+            (40, 'a'),
+            (10, 'b'),
+            (30, 'c'),
+            (50, 'd'),
+            (20, 'e'),
+            (30, 'f'),
+        ] {
+            h.push(key, value);
+        }
+        let mut due = h.pop_le(&30);
+        // Elements with equal keys come out in an unspecified order.
+        due[2..].sort();
+        assert_eq!(due, [(10, 'b'), (20, 'e'), (30, 'c'), (30, 'f')]);
+        assert!(h.pop_le(&30).is_empty());
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.pop(), Some((40, 'a')));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn replace_root() {
+        let mut h = Heap::new();
+        let (old, five) = h.replace_root(5, 'a');
+        assert_eq!(old, None);
+        assert_eq!(h.get(&five), (&5, &'a'));
+        let three = h.push(3, 'b');
+        h.push(7, 'c');
+        let (old, nine) = h.replace_root(9, 'd');
+        assert_eq!(old, Some((3, 'b')));
+        assert_eq!(h.get(&nine), (&9, &'d'));
+        assert_eq!(h.try_get(&three), None);
+        assert_eq!(h.try_remove(&three), None);
+        assert_eq!(h.pop(), Some((5, 'a')));
+        assert_eq!(h.pop(), Some((7, 'c')));
+        assert_eq!(h.remove(nine), (9, 'd'));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn replace_root_matches_pop_and_push() {
+        // Linear congruential generator, for reproducible inputs.
+        let mut seed = 0x2545_f491_u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            seed >> 16
+        };
+        for _ in 0..100 {
+            let mut replaced = Heap::new();
+            let mut popped = Heap::new();
+            for _ in 0..next() % 32 {
+                let i = next() % 100;
+                replaced.push(i, ());
+                popped.push(i, ());
+            }
+            for _ in 0..32 {
+                let i = next() % 100;
+                let expected = popped.pop();
+                popped.push(i, ());
+                assert_eq!(replaced.replace_root(i, ()).0, expected);
+                assert_eq!(replaced.peek(), popped.peek());
+            }
+            while let Some(i) = popped.pop() {
+                assert_eq!(replaced.pop(), Some(i));
+            }
+            assert!(replaced.is_empty());
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn unordered_values() {
+        // `f64` isn't `Ord`, only keys are compared.
+        let mut h = Heap::new();
+        let slot = h.push(2, 0.5);
+        h.push(1, f64::NAN);
+        h.push(3, 1.5);
+        assert_eq!(h.peek().map(|(&key, _)| key), Some(1));
+        assert!(h.pop().unwrap().1.is_nan());
+        assert_eq!(h.get(&slot), (&2, &0.5));
+        assert_eq!(h.pop(), Some((2, 0.5)));
+        assert_eq!(h.pop(), Some((3, 1.5)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn max_heap() {
+        let mut h = MaxHeap::new();
+        h.push(1, 'a');
+        let slot = h.push(8, 'b');
+        h.push(4, 'c');
+        h.push(2, 'd');
+        assert_eq!(h.peek(), Some((&8, &'b')));
+        assert_eq!(h.remove(slot), (8, 'b'));
+        assert_eq!(h.pop(), Some((4, 'c')));
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.pop(), Some((2, 'd')));
+        assert_eq!(h.pop(), Some((1, 'a')));
+        assert_eq!(h.pop(), None);
+        assert!(h.is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn stale_slot() {
+        let mut h = Heap::new();
+        let one = h.push(1, ());
+        let stale = Slot { ..one };
+        assert_eq!(h.remove(one), (1, ()));
+        let two = h.push(2, ());
+
+        // `two` reuses the index of `one`, but not its version.
+        assert_eq!(two.id(), stale.id());
+        assert_eq!(h.try_get(&stale), None);
+        assert_eq!(h.try_remove(&stale), None);
+        assert_eq!(h.try_get(&two), Some((&2, &())));
+        assert_eq!(h.try_remove(&two), Some((2, ())));
+        assert_eq!(h.try_remove(&two), None);
+        assert!(h.is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    #[should_panic(expected = "slot refers to an element which was removed")]
+    fn remove_stale_slot() {
+        let mut h = Heap::new();
+        let one = h.push(1, ());
+        let stale = Slot { ..one };
+        h.remove(one);
+        h.push(2, ());
+        h.remove(stale);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    #[should_panic(expected = "slot used with a heap it wasn't obtained from")]
+    fn remove_foreign_slot() {
+        let mut a = Heap::new();
+        let mut b = Heap::new();
+        a.push(1, ());
+        let slot = b.push(2, ());
+        a.remove(slot);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn retain() {
+        let mut h = Heap::new();
+        let slots: Vec<_> = (0..10).map(|i| h.push(9 - i, i)).collect();
+        h.retain(|key, _| key % 3 != 0);
+        assert_eq!(h.len(), 6);
+        assert_eq!(h.get(&slots[1]), (&8, &1));
+        h.retain(|_, &value| value != 2);
+        assert_eq!(h.len(), 5);
+        assert_eq!(h.remove(slots.into_iter().nth(4).unwrap()), (5, 4));
+        let mut popped = Vec::new();
+        while let Some((i, _)) = h.pop() {
+            popped.push(i);
+        }
+        assert_eq!(popped, vec![1, 2, 4, 8]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn iter_mut() {
+        let mut h = Heap::new();
+        let slots: Vec<_> = (0..10).map(|i| h.push(i, ())).collect();
+        for (i, ()) in h.iter_mut() {
+            *i = 100 - *i;
+        }
+        assert_eq!(h.peek(), Some((&91, &())));
+        assert_eq!(h.remove(slots.into_iter().nth(3).unwrap()), (97, ()));
+        let mut popped = Vec::new();
+        while let Some((i, ())) = h.pop() {
+            popped.push(i);
+        }
+        assert_eq!(popped, vec![91, 92, 93, 94, 95, 96, 98, 99, 100]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    #[should_panic(expected = "slot used with a heap it wasn't obtained from")]
+    fn remove_default_slot() {
+        let mut h = Heap::default();
+        h.push(1, ());
+        h.remove(Slot::default());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn position_and_depth() {
+        let mut h = Heap::new();
+        let five = h.push(5, ());
+        assert_eq!(h.position_of(&five), 0);
+        assert_eq!(h.depth_of(&five), 0);
+        let one = h.push(1, ());
+        let three = h.push(3, ());
+        let four = h.push(4, ());
+        assert_eq!(h.position_of(&one), 0);
+        assert_eq!(h.depth_of(&one), 0);
+        assert_eq!(h.depth_of(&three), 1);
+        assert_eq!(h.position_of(&four), 1);
+        assert_eq!(h.position_of(&five), 3);
+        assert_eq!(h.depth_of(&five), 2);
+        assert_eq!(h.pop(), Some((1, ())));
+        assert_eq!(h.position_of(&three), 0);
+    }
+
+    fn vec2heap<T: Ord>(v: Vec<T>) -> Heap<T, ()> {
         let mut h = Heap::new();
         for t in v {
-            h.push(t);
+            h.push(t, ());
         }
-        return h;
+        h
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_peek_and_pop() {
         let data = vec![2, 4, 6, 2, 1, 8, 10, 3, 5, 7, 0, 9, 1];
         let mut sorted = data.clone();
         sorted.sort();
         let mut heap = vec2heap(data);
         while heap.peek().is_some() {
-            assert_eq!(heap.peek().unwrap(), sorted.first().unwrap());
-            assert_eq!(heap.pop().unwrap(), sorted.remove(0));
+            assert_eq!(heap.peek().unwrap().0, sorted.first().unwrap());
+            assert_eq!(heap.pop().unwrap().0, sorted.remove(0));
         }
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_push() {
         let mut heap = Heap::new();
-        heap.push(-2);
-        heap.push(-4);
-        heap.push(-9);
-        assert!(*heap.peek().unwrap() == -9);
-        heap.push(-11);
-        assert!(*heap.peek().unwrap() == -11);
-        heap.push(-5);
-        assert!(*heap.peek().unwrap() == -11);
-        heap.push(-27);
-        assert!(*heap.peek().unwrap() == -27);
-        heap.push(-3);
-        assert!(*heap.peek().unwrap() == -27);
-        heap.push(-103);
-        assert!(*heap.peek().unwrap() == -103);
+        heap.push(-2, ());
+        heap.push(-4, ());
+        heap.push(-9, ());
+        assert_eq!(heap.peek(), Some((&-9, &())));
+        heap.push(-11, ());
+        assert_eq!(heap.peek(), Some((&-11, &())));
+        heap.push(-5, ());
+        assert_eq!(heap.peek(), Some((&-11, &())));
+        heap.push(-27, ());
+        assert_eq!(heap.peek(), Some((&-27, &())));
+        heap.push(-3, ());
+        assert_eq!(heap.peek(), Some((&-27, &())));
+        heap.push(-103, ());
+        assert_eq!(heap.peek(), Some((&-103, &())));
     }
 
     fn check_to_vec(mut data: Vec<i32>) {
         let mut heap = Heap::new();
         for data in data.iter() {
-            heap.push(*data);
+            heap.push(*data, ());
         }
         data.sort();
         let mut v = Vec::new();
-        while let Some(i) = heap.pop() {
+        while let Some((i, ())) = heap.pop() {
             v.push(i);
         }
         assert_eq!(v, data);
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_to_vec() {
         check_to_vec(vec![]);
         check_to_vec(vec![5]);
@@ -339,15 +1228,184 @@ mod tests {
         check_to_vec(vec![5, 4, 3, 2, 1, 5, 4, 3, 2, 1, 5, 4, 3, 2, 1]);
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_empty_pop() {
-        let mut heap = Heap::<i32>::new();
+        let mut heap = Heap::<i32, ()>::new();
         assert!(heap.pop().is_none());
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_empty_peek() {
-        let empty = Heap::<i32>::new();
+        let empty = Heap::<i32, ()>::new();
         assert!(empty.peek().is_none());
     }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn iter() {
+        let mut h = Heap::new();
+        for i in [5, 3, 8, 1, 9, 2] {
+            h.push(i, ());
+        }
+        let removed = h.push(7, ());
+        h.remove(removed);
+
+        let slots: BTreeMap<i32, Slot> = h.iter().map(|(&i, (), slot)| (i, slot)).collect();
+        assert_eq!(
+            slots.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3, 5, 8, 9]
+        );
+        for (i, slot) in slots {
+            assert_eq!(h.remove(slot), (i, ()));
+        }
+        assert!(h.is_empty());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn remove_all() {
+        let mut h = Heap::new();
+        let slots: Vec<Slot> = (0..10).map(|i| h.push(i, ())).collect();
+        let stale = Slot { ..slots[3] };
+        assert_eq!(h.remove(Slot { ..slots[3] }), (3, ()));
+        let mut other = Heap::new();
+        let foreign = other.push(0, ());
+
+        let doomed = [
+            Slot { ..slots[0] },
+            stale,
+            Slot { ..slots[5] },
+            Slot { ..slots[5] },
+            foreign,
+            Slot { ..slots[9] },
+        ];
+        assert_eq!(h.remove_all(&doomed), 3);
+        assert!(h.is_valid());
+        assert_eq!(h.len(), 6);
+        assert_eq!(h.get(&slots[7]), (&7, &()));
+        let new = h.push(4, ());
+        assert_eq!(h.remove(new), (4, ()));
+
+        let mut remaining = Vec::new();
+        while let Some((i, ())) = h.pop() {
+            remaining.push(i);
+        }
+        assert_eq!(remaining, [1, 2, 4, 6, 7, 8]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn pop_with_slot() {
+        let mut h = Heap::new();
+        let slots: Vec<Slot> = [3, 1, 2].into_iter().map(|i| h.push(i, ())).collect();
+        let (min, (), slot) = h.pop_with_slot().unwrap();
+        assert_eq!(min, 1);
+        assert_eq!(slot.id(), slots[1].id());
+        assert!(h.try_get(&slot).is_none());
+
+        // Pushed back, e.g. after failing to deliver it.
+        let new = h.push(min, ());
+        assert_eq!(new.id(), slot.id());
+        assert!(h.try_get(&slot).is_none());
+        assert_eq!(h.try_get(&new), Some((&1, &())));
+        assert_eq!(h.pop_with_slot().map(|(i, _, _)| i), Some(1));
+        assert_eq!(h.pop(), Some((2, ())));
+        assert_eq!(h.pop(), Some((3, ())));
+        assert!(h.pop_with_slot().is_none());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn fuzz_regressions() {
+        // Inputs exercising removals in the middle of the heap, which move
+        // the last element there and percolate it either way.
+        let inputs: [&[u8]; 4] = [
+            &[],
+            &[0, 5, 0, 3, 0, 9, 0, 1, 0, 7, 2, 1, 1, 1, 1, 1],
+            &[
+                0, 50, 0, 10, 0, 40, 0, 20, 0, 30, 0, 5, 0, 45, 2, 3, 3, 0, 200, 255, 255, 255,
+                255, 255, 255, 255, 1, 2, 0, 1, 1,
+            ],
+            &[0, 1, 0, 1, 0, 1, 3, 2, 9, 3, 0, 0, 2, 4, 1, 1, 1, 1],
+        ];
+        for input in inputs {
+            super::fuzz::run(input);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn raw_parts() {
+        let mut h = Heap::new();
+        let slots: Vec<Slot> = [4, 2, 6, 1].into_iter().map(|i| h.push(i, ())).collect();
+        h.remove(Slot { ..slots[2] });
+        for (item, ()) in h.iter_mut() {
+            *item *= 10;
+        }
+
+        let (items, index, next_index) = unsafe { h.into_raw_parts() };
+        assert_eq!(items[0].0, 10);
+        assert_eq!(index.len(), 4);
+        assert_eq!(next_index, 2);
+        let mut h = unsafe { Heap::from_raw_parts(items, index, next_index) };
+        assert!(h.is_valid());
+        assert!(h.try_get(&slots[0]).is_none());
+        let new = h.push(30, ());
+        assert_eq!(new.id(), 2);
+
+        let mut all = Vec::new();
+        while let Some((i, ())) = h.pop() {
+            all.push(i);
+        }
+        assert_eq!(all, [10, 20, 30, 40]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn memory_usage() {
+        let mut h = Heap::new();
+        assert_eq!(h.memory_usage(), 0);
+        assert_eq!(h.slab_fragmentation(), 0.0);
+        for i in 0..4u64 {
+            h.push(i, ());
+        }
+        let usage = h.memory_usage();
+        assert!(usage >= 4 * core::mem::size_of::<(u64, (), usize)>());
+        assert_eq!(h.slab_fragmentation(), 0.0);
+
+        h.pop();
+        h.pop();
+        assert_eq!(h.slab_fragmentation(), 0.5);
+        // Buffers aren't shrunk.
+        assert_eq!(h.memory_usage(), usage);
+        h.push(7, ());
+        assert_eq!(h.slab_fragmentation(), 0.25);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn is_valid() {
+        let mut h = Heap::new();
+        for i in [5, 3, 8, 1, 9, 2] {
+            h.push(i, ());
+        }
+        assert!(h.is_valid());
+        h.debug_assert_valid();
+
+        // Modifications through `iter_mut` are allowed until the next
+        // mutation restores the heap property.
+        for (item, ()) in h.iter_mut() {
+            *item = 10 - *item;
+        }
+        assert!(h.is_valid());
+        h.push(0, ());
+        assert!(h.is_valid());
+
+        h.items.swap(0, 1);
+        assert!(!h.is_valid());
+    }
+
+    #[cfg(debug_assertions)]
+    #[wasm_bindgen_test(unsupported = test)]
+    #[should_panic(expected = "self.index[j] != i")]
+    fn debug_assert_valid_panics() {
+        let mut h = Heap::new();
+        h.push(1, ());
+        h.push(2, ());
+        h.items.swap(0, 1);
+        h.debug_assert_valid();
+    }
 }
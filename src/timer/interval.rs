@@ -1,12 +1,15 @@
-use pin_utils::unsafe_pinned;
+use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
 use futures::prelude::*;
+use futures::stream::FusedStream;
+#[allow(deprecated)]
+use pin_utils::{unsafe_pinned, unsafe_unpinned};
 
-use crate::timer::delay;
-use crate::{Delay, Instant, TimerHandle};
+use crate::timer::{self, delay, saturating_add};
+use crate::{next_aligned, Delay, Instant, SchedulerHint, TimerHandle};
 
 /// A stream representing notifications at fixed interval
 ///
@@ -14,6 +17,14 @@ use crate::{Delay, Instant, TimerHandle};
 /// `Interval::new_at` methods indicating when a first notification
 /// should be triggered and when it will be repeated.
 ///
+/// If the `Timer` driving an interval is dropped, the stream ends.
+///
+/// Notifications missed while the interval isn't polled, e.g. because its
+/// consumer is busy for several periods, don't queue up by default: the next
+/// poll yields a single notification, and the following one is scheduled at
+/// the next period boundary which is still in the future. See
+/// `set_coalesce_pending` to deliver them all instead.
+///
 /// Note that intervals are not intended for high resolution timers, but rather
 /// they will likely fire some granularity after the exact instant that they're
 /// otherwise indicated to fire at.
@@ -21,18 +32,22 @@ use crate::{Delay, Instant, TimerHandle};
 pub struct Interval {
     delay: Delay,
     interval: Duration,
+    /// Number of ticks left before the stream ends, if it ends at all.
+    remaining: Option<u64>,
+    /// How late the last notification was delivered.
+    last_lateness: Option<Duration>,
+    /// Whether notifications missed while not polled are coalesced into one.
+    coalesce_pending: bool,
 }
 
 impl Interval {
-    unsafe_pinned!(delay: Delay);
-
     /// Creates a new interval which will fire at `dur` time into the future,
     /// and will repeat every `dur` interval after
     ///
     /// The returned object will be bound to the default timer for this thread.
     /// The default timer will be spun up in a helper thread on first use.
     pub fn new(dur: Duration) -> Interval {
-        Interval::new_at(Instant::now() + dur, dur)
+        Interval::new_at(saturating_add(timer::now(), dur), dur)
     }
 
     /// Creates a new interval which will fire at the time specified by `at`,
@@ -44,9 +59,48 @@ impl Interval {
         Interval {
             delay: Delay::new_at(at),
             interval: dur,
+            remaining: None,
+            last_lateness: None,
+            coalesce_pending: true,
         }
     }
 
+    /// Same as `Interval::new`, but fails if the timer backend isn't
+    /// available. See `Delay::try_new`.
+    pub fn try_new(dur: Duration) -> io::Result<Interval> {
+        Interval::try_new_at(saturating_add(timer::now(), dur), dur)
+    }
+
+    /// Same as `Interval::new_at`, but fails if the timer backend isn't
+    /// available. See `Delay::try_new`.
+    pub fn try_new_at(at: Instant, dur: Duration) -> io::Result<Interval> {
+        Ok(Interval {
+            delay: Delay::try_new_at(at)?,
+            interval: dur,
+            remaining: None,
+            last_lateness: None,
+            coalesce_pending: true,
+        })
+    }
+
+    /// Creates a new interval which will fire every `period`, on multiples of
+    /// `period` of the wall clock, e.g. at the start of every minute for
+    /// `Duration::from_secs(60)`.
+    ///
+    /// The first notification is computed with `next_aligned`, and the
+    /// following ones are `period` apart on the monotonic clock. They stay
+    /// aligned as long as the wall clock isn't adjusted; `reset_at` with a new
+    /// `next_aligned` instant realigns the interval.
+    ///
+    /// The returned object will be bound to the default timer for this thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn aligned(period: Duration) -> Interval {
+        Interval::new_at(next_aligned(period), period)
+    }
+
     /// Creates a new interval which will fire at the time specified by `at`,
     /// and then will repeat every `dur` interval after
     ///
@@ -55,66 +109,359 @@ impl Interval {
         Interval {
             delay: Delay::new_handle(at, handle),
             interval: dur,
+            remaining: None,
+            last_lateness: None,
+            coalesce_pending: true,
+        }
+    }
+
+    /// Creates a new interval which fires every `period`, the first time being
+    /// `period` from now, and which is pushed back to `period` from now each
+    /// time `trigger` yields.
+    ///
+    /// This is useful to poll a server regularly, unless it has pushed a
+    /// message recently. The interval keeps going once `trigger` ends.
+    pub fn from_stream<S: Stream<Item = ()>>(trigger: S, period: Duration) -> TriggeredInterval<S> {
+        TriggeredInterval {
+            trigger,
+            trigger_done: false,
+            interval: Interval::new(period),
+        }
+    }
+
+    /// Changes the period of this interval.
+    ///
+    /// The next notification is rescheduled to happen `period` after the
+    /// previous one, rather than `period` from now, so that changing the
+    /// period doesn't shift the phase of the interval. If that instant has
+    /// already passed, the next notification fires immediately, and the
+    /// following ones are spaced from it.
+    pub fn set_period(&mut self, period: Duration) {
+        let now = timer::now();
+        // The previous notification can't be computed for huge periods, which
+        // never fire in practice anyway.
+        let prev = delay::fires_at(&self.delay)
+            .checked_sub(self.interval)
+            .unwrap_or(now);
+        self.interval = period;
+        // Not earlier than now, as the previous notification may be so far in
+        // the past that the periods missed since then can't be counted.
+        self.delay.reset_at(saturating_add(prev, period).max(now));
+    }
+
+    /// Reschedules the next notification to happen at `next`, the following
+    /// ones repeating every period after it.
+    pub fn reset_at(&mut self, next: Instant) {
+        self.delay.reset_at(next);
+    }
+
+    /// Moves the next notification `n` periods later, without firing the
+    /// notifications in between, e.g. to resume an interval whose progress was
+    /// saved. If that instant has already passed, the next notification fires
+    /// immediately.
+    ///
+    /// Skipped notifications don't count towards the limit set with `take`.
+    ///
+    /// As `Interval` is a `Stream`, `interval.skip(n)` resolves to
+    /// `StreamExt::skip` when that trait is in scope; call this method as
+    /// `Interval::skip(&mut interval, n)` then.
+    pub fn skip(&mut self, n: u64) {
+        let skipped = u32::try_from(n)
+            .ok()
+            .and_then(|n| self.interval.checked_mul(n))
+            .unwrap_or(Duration::MAX);
+        let next = delay::fires_at(&self.delay);
+        self.delay.reset_at(saturating_add(next, skipped));
+    }
+
+    /// Sets whether notifications missed while the interval isn't polled are
+    /// coalesced into a single one, which is the default.
+    ///
+    /// This is evaluated each time a notification is polled. When enabled, the
+    /// next notification is scheduled at the first period boundary after the
+    /// poll, however many periods have elapsed. When disabled, it's scheduled
+    /// one period after the previous one, so missed notifications are
+    /// delivered back-to-back until the interval catches up.
+    pub fn set_coalesce_pending(&mut self, coalesce: bool) {
+        self.coalesce_pending = coalesce;
+    }
+
+    /// Returns how long after its scheduled instant the last notification was
+    /// fired by the timer, or `None` if there wasn't any notification yet.
+    ///
+    /// See `Delay::lateness`.
+    pub fn last_lateness(&self) -> Option<Duration> {
+        self.last_lateness
+    }
+
+    /// Limits this interval to `n` more notifications, after which the stream
+    /// ends.
+    ///
+    /// Contrary to `StreamExt::take`, the returned `Interval` reports through
+    /// `FusedStream::is_terminated` when it has ended.
+    pub fn take(mut self, n: u64) -> Interval {
+        self.remaining = Some(n);
+        self
+    }
+
+    /// Polls for the next notification, and returns the instant it was
+    /// scheduled at.
+    ///
+    /// This is the primitive the `Stream` implementation and `tick` are built
+    /// on, for use in hand-written futures and streams. Once the interval has
+    /// ended, see `take`, this always returns `Poll::Pending`.
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if self.remaining == Some(0) {
+            return Poll::Pending;
+        }
+        match Pin::new(&mut self.delay).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(_)) => {
+                // The timer is gone, so there won't be any more notification.
+                self.remaining = Some(0);
+                return Poll::Pending;
+            }
+            Poll::Ready(Ok(())) => {}
+        }
+        self.last_lateness = self.delay.lateness();
+        let at = delay::fires_at(&self.delay);
+        let next = if self.coalesce_pending {
+            next_interval(at, timer::now(), self.interval)
+        } else {
+            saturating_add(at, self.interval)
+        };
+        trace_event!(
+            "interval tick",
+            delay_id = delay::id(&self.delay),
+            deadline = at,
+            next_deadline = next,
+        );
+        self.delay.reset_at(next);
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining -= 1;
+        }
+        Poll::Ready(at)
+    }
+
+    /// Waits for the next notification, and returns the instant it was
+    /// scheduled at.
+    ///
+    /// Once the interval has ended, the returned future never resolves.
+    pub fn tick(&mut self) -> impl Future<Output = Instant> + '_ {
+        future::poll_fn(move |cx| self.poll_tick(cx))
+    }
+
+    /// Converts this interval into a stream of the instants its notifications
+    /// were scheduled at, which ends along with the interval.
+    ///
+    /// Combined with `ScheduledStreamExt::with_schedule_times`, this gives
+    /// the delivery jitter of each notification.
+    pub fn ticks(self) -> IntervalStream {
+        IntervalStream::new(self)
+    }
+
+    /// Converts this interval into a stream of the instants its notifications
+    /// were scheduled at, numbered from 0.
+    ///
+    /// Contrary to `StreamExt::enumerate`, the index is a `u64`, so it doesn't
+    /// overflow on 32-bit targets such as WASM.
+    pub fn zip_with_index(self) -> IndexedInterval {
+        IndexedInterval {
+            interval: self,
+            index: 0,
         }
     }
+
+    /// Sets how the WASM timer should schedule its wake-ups for this interval.
+    ///
+    /// See `Delay::set_scheduler_hint`.
+    pub fn set_scheduler_hint(&mut self, hint: SchedulerHint) {
+        self.delay.set_scheduler_hint(hint);
+    }
 }
 
 impl Stream for Interval {
     type Item = ();
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if Pin::new(&mut *self).delay().poll(cx).is_pending() {
-            return Poll::Pending;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match this.poll_tick(cx) {
+            Poll::Ready(_) => Poll::Ready(Some(())),
+            Poll::Pending if this.is_terminated() => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
         }
-        let next = next_interval(delay::fires_at(&self.delay), Instant::now(), self.interval);
-        self.delay.reset_at(next);
-        Poll::Ready(Some(()))
     }
 }
 
-/// Converts Duration object to raw nanoseconds if possible
+impl FusedStream for Interval {
+    fn is_terminated(&self) -> bool {
+        self.remaining == Some(0)
+    }
+}
+
+/// Creates a new interval which fires every `period`, the first time being
+/// `period` from now.
+///
+/// This is the same as `Interval::new`.
+pub fn interval(period: Duration) -> Interval {
+    Interval::new(period)
+}
+
+/// Creates a new interval which fires at `start`, and then every `period`.
 ///
-/// This is useful to divide intervals.
+/// This is the same as `Interval::new_at`.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    Interval::new_at(start, period)
+}
+
+/// Stream returned by the `Interval::from_stream` method.
+#[derive(Debug)]
+pub struct TriggeredInterval<S> {
+    trigger: S,
+    trigger_done: bool,
+    interval: Interval,
+}
+
+impl<S> TriggeredInterval<S> {
+    #![allow(deprecated)]
+    unsafe_pinned!(trigger: S);
+    unsafe_unpinned!(trigger_done: bool);
+    unsafe_unpinned!(interval: Interval);
+}
+
+impl<S: Stream<Item = ()>> Stream for TriggeredInterval<S> {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        while !self.trigger_done {
+            match self.as_mut().trigger().poll_next(cx) {
+                Poll::Ready(Some(())) => {
+                    let next = saturating_add(timer::now(), self.interval.interval);
+                    self.as_mut().interval().reset_at(next);
+                }
+                Poll::Ready(None) => *self.as_mut().trigger_done() = true,
+                Poll::Pending => break,
+            }
+        }
+        let interval = self.as_mut().interval();
+        match interval.poll_tick(cx) {
+            Poll::Ready(at) => Poll::Ready(Some(at)),
+            Poll::Pending if interval.is_terminated() => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Stream returned by the `Interval::zip_with_index` method.
+#[derive(Debug)]
+pub struct IndexedInterval {
+    interval: Interval,
+    /// Index of the next notification.
+    index: u64,
+}
+
+impl IndexedInterval {
+    /// Returns the underlying interval.
+    pub fn into_inner(self) -> Interval {
+        self.interval
+    }
+}
+
+impl Stream for IndexedInterval {
+    type Item = (u64, Instant);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match this.interval.poll_tick(cx) {
+            Poll::Ready(at) => {
+                let index = this.index;
+                this.index += 1;
+                Poll::Ready(Some((index, at)))
+            }
+            Poll::Pending if this.interval.is_terminated() => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl FusedStream for IndexedInterval {
+    fn is_terminated(&self) -> bool {
+        self.interval.is_terminated()
+    }
+}
+
+/// Stream of the instants the notifications of an `Interval` were scheduled
+/// at, which ends along with the interval.
 ///
-/// While technically for large duration it's impossible to represent any
-/// duration as nanoseconds, the largest duration we can represent is about
-/// 427_000 years. Large enough for any interval we would use or calculate in
-/// tokio.
-fn duration_to_nanos(dur: Duration) -> Option<u64> {
-    dur.as_secs()
-        .checked_mul(1_000_000_000)
-        .and_then(|v| v.checked_add(dur.subsec_nanos() as u64))
-}
-
-fn next_interval(prev: Instant, now: Instant, interval: Duration) -> Instant {
-    let new = prev + interval;
+/// This is the stream returned by `Interval::ticks`, as a named type, e.g. to
+/// be stored in a struct.
+#[derive(Debug)]
+pub struct IntervalStream {
+    interval: Interval,
+}
+
+impl IntervalStream {
+    /// Wraps `interval`.
+    pub fn new(interval: Interval) -> IntervalStream {
+        IntervalStream { interval }
+    }
+
+    /// Returns the underlying interval.
+    pub fn into_inner(self) -> Interval {
+        self.interval
+    }
+}
+
+impl Stream for IntervalStream {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        let this = self.get_mut();
+        match this.interval.poll_tick(cx) {
+            Poll::Ready(at) => Poll::Ready(Some(at)),
+            Poll::Pending if this.interval.is_terminated() => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl FusedStream for IntervalStream {
+    fn is_terminated(&self) -> bool {
+        self.interval.is_terminated()
+    }
+}
+
+pub(super) fn next_interval(prev: Instant, now: Instant, interval: Duration) -> Instant {
+    let new = saturating_add(prev, interval);
     if new > now {
         new
     } else {
-        let spent_ns =
-            duration_to_nanos(now.duration_since(prev)).expect("interval should be expired");
-        let interval_ns =
-            duration_to_nanos(interval).expect("interval is less that 427 thousand years");
-        let mult = spent_ns / interval_ns + 1;
-        assert!(
-            mult < (1 << 32),
-            "can't skip more than 4 billion intervals of {:?} \
-             (trying to skip {})",
-            interval,
-            mult
-        );
-        prev + interval * (mult as u32)
+        // Counted in nanoseconds, any number of periods can be skipped. A zero
+        // period stays on the previous instant.
+        let spent = now.duration_since(prev).as_nanos();
+        let skipped = match spent.checked_div(interval.as_nanos()) {
+            Some(mult) => (mult + 1) * interval.as_nanos(),
+            None => 0,
+        };
+        let skipped = timer::nanos_to_duration(skipped);
+        saturating_add(prev, skipped)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
     use std::time::Duration;
 
+    use futures::channel::mpsc;
+    use futures::prelude::*;
+    use futures::stream::FusedStream;
+    use futures::task::noop_waker;
     use wasm_bindgen_test::wasm_bindgen_test;
 
-    use super::next_interval;
-    use crate::Instant;
+    use super::{interval, interval_at, next_interval, saturating_add};
+    use crate::{Instant, Interval, IntervalStream, SystemTime, Timer, UNIX_EPOCH};
 
     struct Timeline(Instant);
 
@@ -146,7 +493,7 @@ mod test {
         }
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn norm_next() {
         let tm = Timeline::new();
         assert!(almost_eq(
@@ -163,7 +510,7 @@ mod test {
         ));
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn fast_forward() {
         let tm = Timeline::new();
         assert!(almost_eq(
@@ -180,16 +527,339 @@ mod test {
         ));
     }
 
-    /// TODO: this test actually should be successful, but since we can't
-    ///       multiply Duration on anything larger than u32 easily we decided
-    ///       to allow it to fail for now
+    /// Processes pending updates of the timer, then fires everything up to
+    /// `now`.
+    fn advance_to(timer: &mut Timer, now: Instant) {
+        let waker = noop_waker();
+        let _ = Pin::new(&mut *timer).poll(&mut Context::from_waker(&waker));
+        timer.advance_to(now);
+    }
+
+    fn poll_next(interval: &mut Interval) -> Poll<Option<()>> {
+        let waker = noop_waker();
+        interval.poll_next_unpin(&mut Context::from_waker(&waker))
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn set_period() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+
+        assert_eq!(poll_next(&mut interval), Poll::Pending);
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+        advance_to(&mut timer, tm.at(100));
+        assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+
+        interval.set_period(dur(50));
+        assert_eq!(poll_next(&mut interval), Poll::Pending);
+        advance_to(&mut timer, tm.at(149));
+        assert_eq!(poll_next(&mut interval), Poll::Pending);
+        advance_to(&mut timer, tm.at(150));
+        assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+        advance_to(&mut timer, tm.at(199));
+        assert_eq!(poll_next(&mut interval), Poll::Pending);
+        advance_to(&mut timer, tm.at(200));
+        assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn set_period_from_huge_period() {
+        let mut timer = Timer::new();
+        let now = Instant::now();
+        let mut interval = Interval::new_handle(
+            saturating_add(now, Duration::MAX),
+            Duration::MAX,
+            timer.handle(),
+        );
+        interval.set_period(dur(100));
+        assert!(interval.delay.deadline() <= Instant::now() + dur(100));
+        advance_to(&mut timer, Instant::now() + dur(100));
+        assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn reset_at() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        interval.reset_at(tm.at(30));
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(30));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(30)));
+        advance_to(&mut timer, tm.at(130));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(130)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn skip() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(0)));
+        Interval::skip(&mut interval, 3);
+        advance_to(&mut timer, tm.at(399));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(400));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(400)));
+
+        // Skipping to an instant which has passed fires immediately.
+        advance_to(&mut timer, tm.at(900));
+        Interval::skip(&mut interval, 2);
+        advance_to(&mut timer, tm.at(900));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(700)));
+
+        Interval::skip(&mut interval, u64::MAX);
+        advance_to(&mut timer, tm.at(10_000));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Pending);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn coalesces_missed_ticks() {
+        let mut timer = Timer::new();
+        let start = Instant::now();
+        let mut interval = Interval::new_handle(start, dur(10), timer.handle());
+        interval.set_coalesce_pending(true);
+
+        // The consumer is busy for more than five periods.
+        while start.elapsed() < dur(55) {}
+        advance_to(&mut timer, Instant::now());
+        let polled = Instant::now();
+        assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+        assert_eq!(poll_next(&mut interval), Poll::Pending);
+        assert!(interval.delay.deadline() > polled);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn delivers_missed_ticks() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(10), timer.handle());
+        interval.set_coalesce_pending(false);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(0)));
+        // Three periods are missed, and are then delivered back-to-back.
+        for i in 1..=3 {
+            advance_to(&mut timer, tm.at(35));
+            assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(10 * i)));
+        }
+        advance_to(&mut timer, tm.at(35));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(40));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(40)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn interval_functions() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut from_fn = interval_at(tm.at(0), dur(100));
+        let mut from_new = Interval::new_at(tm.at(0), dur(100));
+        let mut relative = interval(Duration::from_secs(7200));
+        for at in [tm.at(0), tm.at(100), tm.at(200)] {
+            advance_to(&mut timer, at);
+            assert_eq!(from_fn.poll_tick(&mut cx), Poll::Ready(at));
+            assert_eq!(from_new.poll_tick(&mut cx), Poll::Ready(at));
+        }
+        assert_eq!(relative.poll_tick(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(0) + Duration::from_secs(3601));
+        assert!(relative.poll_tick(&mut cx).is_ready());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn from_stream() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let tm = Timeline::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (kick, trigger) = mpsc::unbounded();
+        let mut interval = Interval::from_stream(trigger, dur(1000));
+        kick.unbounded_send(()).unwrap();
+        assert_eq!(interval.poll_next_unpin(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(999));
+        assert_eq!(interval.poll_next_unpin(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(2000));
+        let at = match interval.poll_next_unpin(&mut cx) {
+            Poll::Ready(Some(at)) => at,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert!(at >= tm.at(1000));
+
+        // Without the trigger, it keeps ticking.
+        drop(kick);
+        advance_to(&mut timer, at + dur(1000));
+        assert_eq!(
+            interval.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(at + dur(1000)))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn zip_with_index() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+        let mut indexed = interval.take(2).zip_with_index();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert_eq!(indexed.poll_next_unpin(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(
+            indexed.poll_next_unpin(&mut cx),
+            Poll::Ready(Some((0, tm.at(0))))
+        );
+        advance_to(&mut timer, tm.at(100));
+        assert_eq!(
+            indexed.poll_next_unpin(&mut cx),
+            Poll::Ready(Some((1, tm.at(100))))
+        );
+        assert_eq!(indexed.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert!(indexed.is_terminated());
+    }
+
+    #[wasm_bindgen_test]
+    async fn interval_stream() {
+        let stream = IntervalStream::new(Interval::new(dur(10)));
+        let ticks: Vec<Instant> = stream.take(3).collect().await;
+        assert_eq!(ticks.len(), 3);
+        assert!(ticks.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn last_lateness() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert_eq!(interval.last_lateness(), None);
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(0)));
+        assert_eq!(interval.last_lateness(), Some(Duration::ZERO));
+        advance_to(&mut timer, tm.at(130));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(100)));
+        assert_eq!(interval.last_lateness(), Some(dur(30)));
+    }
+
     #[wasm_bindgen_test]
-    #[should_panic(expected = "can't skip more than 4 billion intervals")]
+    async fn aligned() {
+        let mut interval = Interval::aligned(dur(200));
+        let mut prev = None;
+        for _ in 0..3 {
+            let at = interval.tick().await;
+            let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            // Ticks are delivered a bit after the boundary.
+            let past = since_epoch.as_millis() % 200;
+            assert!(past <= 50 || past >= 190, "{} ms past the boundary", past);
+            if let Some(prev) = prev {
+                assert_eq!(at - prev, dur(200));
+            }
+            prev = Some(at);
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn poll_tick() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(0));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(0)));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Pending);
+        advance_to(&mut timer, tm.at(100));
+        assert_eq!(interval.poll_tick(&mut cx), Poll::Ready(tm.at(100)));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn tick() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(10), dur(10), timer.handle());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Step the timer from deadline to deadline, rather than waiting. The
+        // interval is only rescheduled once polled, so the timer may be idle
+        // in between.
+        let mut now = tm.at(0);
+        let mut ticks = Vec::new();
+        while ticks.len() < 3 {
+            let _ = timer.poll_at(now, &mut cx);
+            if let Poll::Ready(at) = interval.tick().poll_unpin(&mut cx) {
+                ticks.push((at, now));
+            }
+            now = timer.next_deadline().unwrap();
+        }
+        assert_eq!(
+            ticks,
+            [
+                (tm.at(10), tm.at(10)),
+                (tm.at(20), tm.at(20)),
+                (tm.at(30), tm.at(30))
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn take() {
+        let mut timer = Timer::new();
+        let tm = Timeline(Instant::now() + Duration::from_secs(3600));
+        let mut interval = Interval::new_handle(tm.at(0), dur(100), timer.handle()).take(3);
+        assert!(!interval.is_terminated());
+
+        for i in 0..3 {
+            assert_eq!(poll_next(&mut interval), Poll::Pending);
+            advance_to(&mut timer, tm.at(i * 100));
+            assert_eq!(poll_next(&mut interval), Poll::Ready(Some(())));
+        }
+        assert!(interval.is_terminated());
+        assert_eq!(poll_next(&mut interval), Poll::Ready(None));
+
+        let interval = Interval::new_handle(tm.at(0), dur(100), timer.handle());
+        assert!(!interval.is_terminated());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn advance_near_max_instant() {
+        let max = saturating_add(Instant::now(), Duration::MAX);
+        let next = next_interval(max, Instant::now(), Duration::from_millis(10));
+        assert!(next >= max);
+        let next = next_interval(next, next, Duration::from_secs(1));
+        assert!(next >= max);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
     fn large_skip() {
         let tm = Timeline::new();
         assert_eq!(
             next_interval(tm.at_ns(0, 1), tm.at_ns(25, 0), Duration::new(0, 2)),
             tm.at_ns(25, 1)
         );
+        let max = saturating_add(tm.at(0), Duration::MAX);
+        assert_eq!(next_interval(tm.at(0), max, Duration::new(0, 1)), max);
     }
 }
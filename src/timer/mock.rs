@@ -0,0 +1,158 @@
+//! A clock which only moves when told to, for deterministic tests.
+
+use std::cell::Cell;
+use std::fmt;
+use std::time::Duration;
+
+use crate::timer::{saturating_add, DefaultGuard, Timer, TimerHandle};
+use crate::Instant;
+
+thread_local! {
+    /// Current instant of the mock clock installed on this thread, if any.
+    static MOCK_NOW: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Returns the current instant of the mock clock installed on this thread.
+pub(crate) fn now() -> Option<Instant> {
+    MOCK_NOW.with(Cell::get)
+}
+
+/// A clock which only moves when `advance` is called, firing the delays that
+/// became due.
+///
+/// While a `MockClock` is installed on a thread, the delays, intervals and
+/// tickers created on that thread are bound to a timer owned by the mock, and
+/// the instants they are computed from come from the mock, so tests of
+/// timer-based logic don't depend on real time. Note that `Instant::now` and
+/// `SystemTime::now` themselves still return the real time.
+///
+/// ```
+/// use std::task::{Context, Poll};
+/// use std::time::Duration;
+/// use futures::task::noop_waker;
+/// use futures::FutureExt;
+/// use zduny_wasm_timer::{Delay, MockClock};
+///
+/// let mut clock = MockClock::install();
+/// let mut delay = Delay::new(Duration::from_secs(60));
+/// let waker = noop_waker();
+/// let mut cx = Context::from_waker(&waker);
+/// assert!(delay.poll_unpin(&mut cx).is_pending());
+/// clock.advance(Duration::from_secs(60));
+/// assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+/// ```
+pub struct MockClock {
+    // Declared first so that the default handle is restored before the timer
+    // is dropped.
+    _guard: DefaultGuard,
+    timer: Timer,
+    /// Instant of the mock clock installed before this one, if any.
+    prev: Option<Instant>,
+}
+
+impl MockClock {
+    /// Installs a mock clock on the current thread, starting at the current
+    /// instant, until the returned value is dropped.
+    ///
+    /// Mock clocks can be nested, in which case the innermost one is used.
+    pub fn install() -> MockClock {
+        let start = now().unwrap_or_else(Instant::now);
+        let timer = Timer::new();
+        let guard = timer.handle().set_default();
+        let prev = MOCK_NOW.with(|mock| mock.replace(Some(start)));
+        MockClock {
+            _guard: guard,
+            timer,
+            prev,
+        }
+    }
+
+    /// Returns the current instant of this clock.
+    pub fn now(&self) -> Instant {
+        now().expect("mock clock is installed")
+    }
+
+    /// Moves this clock forward by `dur`, and completes the delays which are
+    /// due at the new instant.
+    ///
+    /// Returns how many delays were completed.
+    pub fn advance(&mut self, dur: Duration) -> usize {
+        let now = saturating_add(self.now(), dur);
+        MOCK_NOW.with(|mock| mock.set(Some(now)));
+        self.timer.fire_expired(now)
+    }
+
+    /// Returns a handle to the timer driven by this clock, e.g. to bind delays
+    /// created on other threads to it.
+    pub fn handle(&self) -> TimerHandle {
+        self.timer.handle()
+    }
+}
+
+impl Drop for MockClock {
+    fn drop(&mut self) {
+        MOCK_NOW.with(|mock| mock.set(self.prev));
+    }
+}
+
+impl fmt::Debug for MockClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockClock")
+            .field("now", &self.now())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::MockClock;
+    use crate::{Delay, Interval};
+
+    #[wasm_bindgen_test]
+    fn advancing_fires_delays() {
+        let mut clock = MockClock::install();
+        let start = clock.now();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut delay = Delay::new(Duration::from_secs(3600));
+        let mut interval = Interval::new(Duration::from_secs(1800));
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert!(interval.poll_tick(&mut cx).is_pending());
+
+        assert_eq!(clock.advance(Duration::from_secs(1800)), 1);
+        assert!(delay.poll_unpin(&mut cx).is_pending());
+        assert_eq!(
+            interval.poll_tick(&mut cx),
+            Poll::Ready(start + Duration::from_secs(1800))
+        );
+
+        assert_eq!(clock.advance(Duration::from_secs(1800)), 2);
+        assert!(matches!(delay.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(delay.lateness(), Some(Duration::ZERO));
+        assert_eq!(
+            interval.poll_tick(&mut cx),
+            Poll::Ready(start + Duration::from_secs(3600))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn nested_clocks() {
+        let outer = MockClock::install();
+        let start = outer.now();
+        {
+            let mut inner = MockClock::install();
+            assert_eq!(inner.now(), start);
+            inner.advance(Duration::from_secs(5));
+            assert_eq!(inner.now(), start + Duration::from_secs(5));
+        }
+        assert_eq!(outer.now(), start);
+    }
+}
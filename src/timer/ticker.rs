@@ -0,0 +1,180 @@
+//! A single type for one-shot and recurring timers.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::prelude::*;
+use futures::stream::FusedStream;
+
+use crate::timer::{self, interval::next_interval};
+use crate::{Delay, Instant};
+
+/// A timer which fires either once or repeatedly, depending on its mode.
+///
+/// A `Ticker` is both a `Future`, which resolves on the next tick, and a
+/// `Stream`, which yields one item per tick. In `Once` mode the stream ends
+/// after the first tick, so the same type can be used wherever either a
+/// `Delay` or an `Interval` would be.
+///
+/// This type is called `Ticker` rather than `Timer`, as `Timer` is the timer
+/// heap driving all delays.
+#[derive(Debug)]
+pub struct Ticker {
+    delay: Delay,
+    mode: TickerMode,
+    done: bool,
+}
+
+/// Whether a `Ticker` fires once or repeatedly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TickerMode {
+    /// Fires once at the given instant.
+    Once(Instant),
+    /// Fires every `period`, the next time being at `next`.
+    Repeating {
+        /// Time between two ticks.
+        period: Duration,
+        /// Instant of the next tick.
+        next: Instant,
+    },
+}
+
+impl Ticker {
+    /// Creates a ticker which fires once, `duration` from now.
+    pub fn once(duration: Duration) -> Ticker {
+        let at = timer::saturating_add(timer::now(), duration);
+        Ticker::new(TickerMode::Once(at))
+    }
+
+    /// Creates a ticker which fires every `period`, starting `period` from
+    /// now.
+    pub fn repeating(period: Duration) -> Ticker {
+        Ticker::new(TickerMode::Repeating {
+            period,
+            next: timer::saturating_add(timer::now(), period),
+        })
+    }
+
+    /// Creates a ticker with the given mode.
+    ///
+    /// The returned object will be bound to the default timer for this thread.
+    pub fn new(mode: TickerMode) -> Ticker {
+        let at = match mode {
+            TickerMode::Once(at) => at,
+            TickerMode::Repeating { next, .. } => next,
+        };
+        Ticker {
+            delay: Delay::new_at(at),
+            mode,
+            done: false,
+        }
+    }
+
+    /// Returns the mode of this ticker.
+    pub fn mode(&self) -> TickerMode {
+        self.mode
+    }
+
+    /// Polls for the next tick. Returns `Poll::Ready(None)` once a `Once`
+    /// ticker has fired, or once the timer driving the ticker is gone.
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match Pin::new(&mut self.delay).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(_)) => {
+                // The timer is gone, so there won't be any more tick.
+                self.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Ready(Ok(())) => {}
+        }
+        match self.mode {
+            TickerMode::Once(_) => self.done = true,
+            TickerMode::Repeating {
+                period,
+                ref mut next,
+            } => {
+                *next = next_interval(*next, timer::now(), period);
+                self.delay.reset_at(*next);
+            }
+        }
+        Poll::Ready(Some(()))
+    }
+}
+
+impl Future for Ticker {
+    type Output = ();
+
+    /// Resolves on the next tick. A `Once` ticker which has already fired
+    /// never resolves again.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.get_mut().poll_tick(cx) {
+            Poll::Ready(Some(())) => Poll::Ready(()),
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Stream for Ticker {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_tick(cx)
+    }
+}
+
+impl FusedStream for Ticker {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::stream::FusedStream;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{Ticker, TickerMode};
+    use crate::Timer;
+
+    #[wasm_bindgen_test]
+    async fn once_ends_after_first_tick() {
+        let mut ticker = Ticker::once(Duration::from_millis(10));
+        assert!(matches!(ticker.mode(), TickerMode::Once(_)));
+        assert_eq!(ticker.next().await, Some(()));
+        assert!(ticker.is_terminated());
+        assert_eq!(ticker.next().await, None);
+    }
+
+    #[wasm_bindgen_test]
+    async fn repeating_ticks() {
+        let mut ticker = Ticker::repeating(Duration::from_millis(5));
+        (&mut ticker).await;
+        assert_eq!(ticker.next().await, Some(()));
+        assert_eq!(ticker.next().await, Some(()));
+        assert!(!ticker.is_terminated());
+    }
+
+    #[wasm_bindgen_test]
+    fn ends_when_timer_dropped() {
+        let timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let mut ticker = Ticker::repeating(Duration::from_secs(3600));
+        drop(timer);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(ticker.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert!(ticker.is_terminated());
+        assert_eq!(ticker.poll_next_unpin(&mut cx), Poll::Ready(None));
+    }
+}
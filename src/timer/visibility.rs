@@ -0,0 +1,133 @@
+//! Mitigation for the throttling of timers in background tabs.
+//!
+//! Browsers throttle timers of hidden pages to once per second or worse,
+//! which makes them drift and then fire in a burst once the page becomes
+//! visible again. Timers which track the visibility of the page catch up
+//! immediately when it becomes visible.
+
+use std::cell::{Cell, RefCell};
+use std::sync::{Arc, Weak};
+
+use wasm_bindgen::{closure::Closure, JsCast};
+
+use super::Inner;
+
+thread_local! {
+    /// Listeners currently registered.
+    static LISTENERS: RefCell<Vec<Listener>> = const { RefCell::new(Vec::new()) };
+
+    /// Whether the page was hidden the last time a listener was notified.
+    static HIDDEN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A `visibilitychange` listener registered for a timer.
+struct Listener {
+    /// Address of the `Inner` of the timer.
+    key: usize,
+    callback: Closure<dyn FnMut()>,
+}
+
+/// Registers a `visibilitychange` listener for the given timer, unless one is
+/// already registered. Does nothing outside of a document, e.g. in workers.
+pub(crate) fn track(inner: &Arc<Inner>) {
+    let document = match web_sys::window().and_then(|window| window.document()) {
+        Some(document) => document,
+        None => return,
+    };
+    let key = Arc::as_ptr(inner) as usize;
+    if LISTENERS.with(|listeners| listeners.borrow().iter().any(|l| l.key == key)) {
+        return;
+    }
+
+    let weak = Arc::downgrade(inner);
+    let callback = {
+        let document = document.clone();
+        Closure::wrap(
+            Box::new(move || on_visibility_change(&weak, document.hidden())) as Box<dyn FnMut()>,
+        )
+    };
+    document
+        .add_event_listener_with_callback("visibilitychange", callback.as_ref().unchecked_ref())
+        .expect("failed to add a visibilitychange listener");
+    HIDDEN.with(|hidden| hidden.set(document.hidden()));
+    LISTENERS.with(|listeners| listeners.borrow_mut().push(Listener { key, callback }));
+}
+
+/// Removes the listener registered for the given timer, if any.
+pub(crate) fn untrack(inner: &Arc<Inner>) {
+    let key = Arc::as_ptr(inner) as usize;
+    let callback = LISTENERS.with(|listeners| {
+        let mut listeners = listeners.borrow_mut();
+        let pos = listeners.iter().position(|l| l.key == key)?;
+        Some(listeners.swap_remove(pos).callback)
+    });
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return,
+    };
+    if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+        let _ = document.remove_event_listener_with_callback(
+            "visibilitychange",
+            callback.as_ref().unchecked_ref(),
+        );
+    }
+}
+
+/// Returns true if a tracked timer was told that the page is hidden.
+pub(crate) fn is_hidden() -> bool {
+    HIDDEN.with(|hidden| hidden.get())
+}
+
+/// Handles a change of visibility of the page.
+///
+/// When the page becomes visible, the task driving the timer is woken up so
+/// that it fires everything that is overdue and re-arms itself precisely.
+pub(crate) fn on_visibility_change(inner: &Weak<Inner>, hidden: bool) {
+    HIDDEN.with(|h| h.set(hidden));
+    if hidden {
+        return;
+    }
+    if let Some(inner) = inner.upgrade() {
+        inner.waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    use super::{is_hidden, on_visibility_change};
+    use crate::{Delay, Instant, TimerHandle};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn overdue_delays_fire_when_visible() {
+        let handle = TimerHandle::default();
+        handle.track_visibility();
+        let mut delay = Delay::new(Duration::from_millis(10));
+
+        // Block the event loop, so that no `setTimeout` gets a chance to run.
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(30) {}
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+
+        on_visibility_change(&handle.inner, true);
+        assert!(is_hidden());
+        on_visibility_change(&handle.inner, false);
+        assert!(!is_hidden());
+        assert!(matches!(
+            Pin::new(&mut delay).poll(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+//! Conversions between the wall clock and the monotonic clock.
+
+use std::time::Duration;
+
+use crate::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Converts a `SystemTime` to the corresponding `Instant`, e.g. to compute a
+/// deadline from a stored UNIX timestamp.
+///
+/// The offset between both clocks is computed as
+/// `st - SystemTime::now() + Instant::now()`. As the two clocks are read one
+/// after the other, and the system clock may be adjusted at any time, the
+/// result is only as precise as the system clock is consistent.
+///
+/// Returns `None` if the result can't be represented as an `Instant`.
+pub fn instant_from_system_time(st: SystemTime) -> Option<Instant> {
+    let sys_now = SystemTime::now();
+    let now = Instant::now();
+    match st.duration_since(sys_now) {
+        Ok(ahead) => now.checked_add(ahead),
+        Err(_) => now.checked_sub(sys_now.duration_since(st).ok()?),
+    }
+}
+
+/// Returns the next instant aligned on a multiple of `period` of the wall
+/// clock, e.g. the top of the next minute for `Duration::from_secs(60)`.
+///
+/// Combined with `Interval::new_at`, this gives intervals firing on wall clock
+/// boundaries. The same imprecision as with `instant_from_system_time` applies.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn next_aligned(period: Duration) -> Instant {
+    assert!(period > Duration::new(0, 0), "period must be non-zero");
+    let sys_now = SystemTime::now();
+    let now = Instant::now();
+    let since_epoch = sys_now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::new(0, 0));
+    let offset = since_epoch.as_nanos() % period.as_nanos();
+    // `offset` is smaller than `period`, so it fits in a `Duration`.
+    let offset = Duration::new(
+        (offset / 1_000_000_000) as u64,
+        (offset % 1_000_000_000) as u32,
+    );
+    now + (period - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{instant_from_system_time, next_aligned};
+    use crate::{Instant, SystemTime, UNIX_EPOCH};
+
+    #[wasm_bindgen_test]
+    fn converts_system_time() {
+        let now = Instant::now();
+        let at = instant_from_system_time(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        assert!(at >= now + Duration::from_secs(59));
+        assert!(at <= now + Duration::from_secs(61));
+    }
+
+    #[wasm_bindgen_test]
+    fn aligned_on_seconds() {
+        let now = Instant::now();
+        let sys_now = SystemTime::now();
+        let at = next_aligned(Duration::from_secs(1));
+        assert!(at > now);
+        assert!(at - now <= Duration::from_secs(1));
+
+        let sys_at = sys_now + (at - now);
+        let millis = sys_at.duration_since(UNIX_EPOCH).unwrap().subsec_millis();
+        assert!(
+            millis <= 10 || millis >= 990,
+            "{} ms past the second",
+            millis
+        );
+    }
+}
@@ -29,6 +29,8 @@ use js_sys::Reflect;
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::Performance;
 
+use crate::DurationExt;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Instant {
     /// Unit is milliseconds.
@@ -58,6 +60,13 @@ impl Ord for Instant {
 }
 
 impl Instant {
+    /// The latest instant that can be represented, e.g. to initialize a
+    /// deadline which isn't known yet.
+    pub const MAX: Instant = Instant { inner: f64::MAX };
+
+    /// The time origin, i.e. the earliest instant returned by `now`.
+    pub const MIN: Instant = Instant { inner: 0.0 };
+
     pub fn now() -> Instant {
         let val = Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
             .expect("performance object not available")
@@ -66,13 +75,66 @@ impl Instant {
         Instant { inner: val }
     }
 
+    /// Returns the amount of time elapsed from `earlier` to this instant, or
+    /// zero if `earlier` is later than this instant.
+    ///
+    /// `performance.now()` is meant to be monotonic, but browsers coarsen and
+    /// jitter its value, so two instants taken in a row may appear slightly
+    /// out of order. Like `std::time::Instant`, this saturates to zero rather
+    /// than panicking in that case. Debug builds still assert that `earlier`
+    /// isn't later by a millisecond or more, which points to a logic error
+    /// rather than to the resolution of the clock. Use
+    /// `checked_duration_since` to tell the cases apart.
     pub fn duration_since(&self, earlier: Instant) -> Duration {
-        *self - earlier
+        self.checked_duration_since(earlier).unwrap_or_else(|| {
+            debug_assert!(
+                earlier.inner - self.inner < 1.0,
+                "`earlier` is {} ms later than this instant",
+                earlier.inner - self.inner
+            );
+            Duration::ZERO
+        })
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to this instant, or
+    /// `None` if `earlier` is later than this instant.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        let ms = self.inner - earlier.inner;
+        if ms >= 0.0 {
+            Some(Duration::from_millis(ms as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to this instant, or
+    /// zero if `earlier` is later than this instant, without asserting
+    /// anything.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
     }
 
     pub fn elapsed(&self) -> Duration {
         Instant::now() - *self
     }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        Some(*self + duration)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        Some(*self - duration)
+    }
+
+    /// Returns the time elapsed since `performance.timeOrigin`, i.e. since the
+    /// browsing context or worker was created.
+    ///
+    /// This is the value of `performance.now()` converted to a `Duration`,
+    /// and is convenient for logging timestamps without having to save a
+    /// reference `Instant` when the module is loaded.
+    pub fn elapsed_since_origin() -> Duration {
+        Duration::from_millis_f64(Instant::now().inner)
+    }
 }
 
 impl Add<Duration> for Instant {
@@ -93,13 +155,23 @@ impl Sub<Duration> for Instant {
     }
 }
 
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
 impl Sub<Instant> for Instant {
     type Output = Duration;
 
     fn sub(self, other: Instant) -> Duration {
-        let ms = self.inner - other.inner;
-        assert!(ms >= 0.0);
-        Duration::from_millis(ms as u64)
+        self.duration_since(other)
     }
 }
 
@@ -202,3 +274,40 @@ impl SubAssign<Duration> for SystemTime {
         *self = *self - rhs;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::Instant;
+
+    #[wasm_bindgen_test]
+    fn extreme_values() {
+        let now = Instant::now();
+        assert!(Instant::MIN <= now);
+        assert!(now < Instant::MAX);
+        assert_eq!(Instant::MIN.saturating_duration_since(now), Duration::ZERO);
+        assert!(Instant::MAX.duration_since(now) > Duration::from_secs(u32::MAX.into()));
+        assert_eq!(Instant::MAX + Duration::from_secs(1), Instant::MAX);
+    }
+
+    #[wasm_bindgen_test]
+    fn duration_since_saturates() {
+        let earlier = Instant::now();
+        let later = Instant {
+            inner: earlier.inner + 0.25,
+        };
+        assert_eq!(earlier.duration_since(later), Duration::ZERO);
+        assert_eq!(earlier - later, Duration::ZERO);
+        assert_eq!(earlier.checked_duration_since(later), None);
+        assert_eq!(later.checked_duration_since(earlier), Some(Duration::ZERO));
+        let much_later = earlier + Duration::from_secs(1);
+        assert_eq!(
+            earlier.saturating_duration_since(much_later),
+            Duration::ZERO
+        );
+        assert_eq!(much_later.duration_since(earlier), Duration::from_secs(1));
+    }
+}
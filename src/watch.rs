@@ -0,0 +1,180 @@
+//! Sharing the ticks of an interval between many observers.
+//!
+//! The `Sender` returned by `channel` drives an `Interval`, typically in a
+//! spawned task, and publishes each of its ticks to the `Receiver`s, which can
+//! be cloned freely.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use zduny_wasm_timer::watch;
+//!
+//! # fn spawn(_: impl std::future::Future<Output = ()> + 'static) {}
+//! # async fn example() {
+//! let (sender, mut receiver) = watch::channel(Duration::from_secs(1));
+//! spawn(sender.run());
+//! let at = receiver.changed().await;
+//! assert_eq!(receiver.last_tick(), Some(at));
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use futures::prelude::*;
+
+use crate::{Instant, Interval};
+
+/// Creates an interval firing every `period`, along with a receiver of its
+/// ticks.
+///
+/// The interval is bound to the default timer, and only ticks while the
+/// `Sender` is run.
+pub fn channel(period: Duration) -> (Sender, Receiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        last: None,
+        version: 0,
+        wakers: Vec::new(),
+    }));
+    let sender = Sender {
+        interval: Interval::new(period),
+        shared: shared.clone(),
+    };
+    (sender, Receiver { shared, seen: 0 })
+}
+
+struct Shared {
+    last: Option<Instant>,
+    /// Number of ticks published so far.
+    version: u64,
+    /// Wakers of the receivers waiting for the next tick.
+    wakers: Vec<Waker>,
+}
+
+/// Driving side of a watch channel, created by `channel`.
+#[derive(Debug)]
+pub struct Sender {
+    interval: Interval,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Sender {
+    /// Drives the interval, publishing each of its ticks to the receivers.
+    ///
+    /// The returned future only resolves when the interval ends, which
+    /// happens if its timer is gone.
+    pub async fn run(self) {
+        let shared = self.shared;
+        let mut ticks = self.interval.ticks();
+        while let Some(at) = ticks.next().await {
+            let mut shared = shared.lock().unwrap();
+            shared.last = Some(at);
+            shared.version += 1;
+            for waker in shared.wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Observing side of a watch channel, created by `channel`.
+///
+/// Each clone keeps track of the ticks it has seen on its own.
+#[derive(Clone)]
+pub struct Receiver {
+    shared: Arc<Mutex<Shared>>,
+    /// Version of the last tick returned by `changed`.
+    seen: u64,
+}
+
+impl Receiver {
+    /// Returns the instant the last tick was scheduled at, or `None` if there
+    /// wasn't any tick yet.
+    pub fn last_tick(&self) -> Option<Instant> {
+        self.shared.lock().unwrap().last
+    }
+
+    /// Waits for a tick this receiver hasn't seen through `changed` yet, and
+    /// returns the instant it was scheduled at.
+    ///
+    /// Ticks which happened while nobody waited aren't queued: only the last
+    /// one is returned. Once the `Sender` is gone, the returned future never
+    /// resolves.
+    pub fn changed(&mut self) -> impl Future<Output = Instant> + '_ {
+        future::poll_fn(move |cx| {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.version != self.seen {
+                self.seen = shared.version;
+                return Poll::Ready(shared.last.unwrap());
+            }
+            if !shared.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                shared.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("last", &self.last)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Receiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("last_tick", &self.last_tick())
+            .field("seen", &self.seen)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::prelude::*;
+    use futures::task::noop_waker;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::channel;
+    use crate::{Instant, Timer};
+
+    #[wasm_bindgen_test]
+    fn receivers_observe_ticks() {
+        let mut timer = Timer::new();
+        let _guard = timer.handle().set_default();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let (sender, mut receiver) = channel(Duration::from_secs(10));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut other = receiver.clone();
+        let mut sender = pin!(sender.run());
+        assert_eq!(receiver.last_tick(), None);
+        assert!(receiver.changed().poll_unpin(&mut cx).is_pending());
+
+        timer.fire_expired(deadline);
+        assert!(sender.as_mut().poll(&mut cx).is_pending());
+        let first = receiver.last_tick().unwrap();
+        assert!(first <= deadline);
+        assert_eq!(receiver.changed().poll_unpin(&mut cx), Poll::Ready(first));
+        assert!(receiver.changed().poll_unpin(&mut cx).is_pending());
+
+        timer.fire_expired(first + Duration::from_secs(10));
+        assert!(sender.as_mut().poll(&mut cx).is_pending());
+        let second = first + Duration::from_secs(10);
+        assert_eq!(receiver.changed().poll_unpin(&mut cx), Poll::Ready(second));
+        // The first tick was missed by the other receiver.
+        assert_eq!(other.changed().poll_unpin(&mut cx), Poll::Ready(second));
+        assert_eq!(other.last_tick(), Some(second));
+    }
+}
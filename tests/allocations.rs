@@ -0,0 +1,65 @@
+//! Arming and cancelling delays doesn't allocate once the timer is warmed up,
+//! as the nodes of dropped delays are reused.
+
+#![cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::pin::Pin;
+use std::task::Context;
+use std::time::Duration;
+
+use futures::task::noop_waker;
+use futures::Future;
+use zduny_wasm_timer::{Delay, Instant, Timer};
+
+/// Allocator counting the allocations made by the current thread.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn arm_and_cancel() {
+    let mut timer = Timer::new();
+    let handle = timer.handle();
+    let at = Instant::now() + Duration::from_secs(3600);
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    let mut cycle = |timer: &mut Timer| {
+        let mut delay = Delay::new_handle(at, handle.clone());
+        assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+        timer.fire_expired(at - Duration::from_secs(1));
+        drop(delay);
+        timer.fire_expired(at - Duration::from_secs(1));
+    };
+    for _ in 0..16 {
+        cycle(&mut timer);
+    }
+
+    let before = allocations();
+    for _ in 0..1_000 {
+        cycle(&mut timer);
+    }
+    assert_eq!(allocations() - before, 0);
+}